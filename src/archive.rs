@@ -0,0 +1,190 @@
+extern crate flate2;
+
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use chrono::Datelike;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+
+use super::history::Message;
+use super::storage;
+
+// Pruned messages are kept in one file per month next to the history
+// e.g. history.json is archived into history.2026-10.json or history.2026-10.json.gz
+
+pub fn archive_messages(history_path: &Path, messages: Vec<Message>, compress: bool) -> Result<(), io::Error> {
+
+    let mut months: Vec<(i32, u32)> = messages.iter().map(|m| (m.date().year(), m.date().month())).collect();
+    months.sort();
+    months.dedup();
+
+    for (year, month) in months {
+        let mut archived = read_month(history_path, year, month)?;
+
+        for message in messages.iter().filter(|m| m.date().year() == year && m.date().month() == month) {
+            // A message can already be there if the history was not saved after archiving
+            // Another message with the same ID is still kept
+            if !archived.iter().any(|a| a.id() == message.id() && a.date() == message.date() && a.text() == message.text()) {
+                archived.push(message.clone());
+            }
+        }
+
+        write_month(history_path, year, month, archived, compress)?;
+    }
+
+    Ok(())
+}
+
+pub fn load_archives(history_path: &Path) -> Result<Vec<Message>, io::Error> {
+
+    let mut messages: Vec<Message> = Vec::new();
+
+    for (path, _, _) in archive_files(history_path)? {
+        messages.append(&mut read_archive(&path)?);
+    }
+
+    Ok(messages)
+}
+
+// Copy of an archived message, it stays in the archive until removed
+pub fn find_message(history_path: &Path, id: u64) -> Result<Message, io::Error> {
+
+    for (_, year, month) in archive_files(history_path)? {
+        if let Some(message) = read_month(history_path, year, month)?.into_iter().find(|m| m.id() == id) {
+            return Ok(message);
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::NotFound, format!("No archived message with ID {}", id)))
+}
+
+// Once the message is safely back in the history
+pub fn remove_message(history_path: &Path, id: u64, compress: bool) -> Result<(), io::Error> {
+
+    for (_, year, month) in archive_files(history_path)? {
+        let mut archived = read_month(history_path, year, month)?;
+
+        if let Some(index) = archived.iter().position(|m| m.id() == id) {
+            archived.remove(index);
+            return write_month(history_path, year, month, archived, compress);
+        }
+    }
+
+    Ok(())
+}
+
+fn archive_path(history_path: &Path, year: i32, month: u32, compress: bool) -> PathBuf {
+
+    let stem = history_path.file_stem().and_then(|s| s.to_str()).unwrap_or("history");
+    let extension = if compress { "json.gz" } else { "json" };
+
+    history_path.with_file_name(format!("{}.{:04}-{:02}.{}", stem, year, month, extension))
+}
+
+// Every archive file of the history with its month
+fn archive_files(history_path: &Path) -> Result<Vec<(PathBuf, i32, u32)>, io::Error> {
+
+    let mut files: Vec<(PathBuf, i32, u32)> = Vec::new();
+
+    let parent = match history_path.parent() {
+        Some(parent) => parent,
+        None => return Ok(files),
+    };
+
+    if fs::metadata(parent).is_err() {
+        return Ok(files);
+    }
+
+    let stem = history_path.file_stem().and_then(|s| s.to_str()).unwrap_or("history");
+    let prefix = format!("{}.", stem);
+
+    for entry in fs::read_dir(parent)? {
+        let path = entry?.path();
+        let name = match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name,
+            None => continue,
+        };
+
+        let month = match name.strip_prefix(&prefix) {
+            Some(rest) => rest.strip_suffix(".json.gz").or_else(|| rest.strip_suffix(".json")),
+            None => None,
+        };
+
+        if let Some(month) = month {
+            if let (Some(year), Some(month)) = (month.get(0..4), month.get(5..7)) {
+                if let (Ok(year), Ok(month)) = (year.parse::<i32>(), month.parse::<u32>()) {
+                    files.push((path.clone(), year, month));
+                }
+            }
+        }
+    }
+
+    files.sort_by_key(|(_, year, month)| (*year, *month));
+
+    Ok(files)
+}
+
+// Reads both variants so changing the compression setting loses nothing
+fn read_month(history_path: &Path, year: i32, month: u32) -> Result<Vec<Message>, io::Error> {
+
+    let mut messages: Vec<Message> = Vec::new();
+
+    for compress in [false, true].iter() {
+        let path = archive_path(history_path, year, month, *compress);
+        if fs::metadata(&path).is_ok() {
+            messages.append(&mut read_archive(&path)?);
+        }
+    }
+
+    Ok(messages)
+}
+
+fn write_month(history_path: &Path, year: i32, month: u32, mut messages: Vec<Message>, compress: bool) -> Result<(), io::Error> {
+
+    let path = archive_path(history_path, year, month, compress);
+    let other_path = archive_path(history_path, year, month, !compress);
+
+    if messages.is_empty() {
+        remove_if_exists(&path)?;
+    } else {
+        messages.sort_by_key(|m| (m.date(), m.id()));
+        let json_archive = serde_json::to_string(&messages)?;
+
+        if compress {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(json_archive.as_bytes())?;
+            storage::write_atomic(&path, &encoder.finish()?)?;
+        } else {
+            storage::write_atomic(&path, json_archive.as_bytes())?;
+        }
+    }
+
+    remove_if_exists(&other_path)?;
+
+    Ok(())
+}
+
+fn read_archive(path: &Path) -> Result<Vec<Message>, io::Error> {
+
+    let mut json_archive = String::new();
+
+    if path.extension().is_some_and(|e| e == "gz") {
+        GzDecoder::new(File::open(path)?).read_to_string(&mut json_archive)?;
+    } else {
+        File::open(path)?.read_to_string(&mut json_archive)?;
+    }
+
+    Ok(serde_json::from_str(&json_archive)?)
+}
+
+fn remove_if_exists(path: &Path) -> Result<(), io::Error> {
+    match fs::remove_file(path) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
@@ -1,4 +1,12 @@
 use std::env;
+use std::error::Error;
+
+use super::clock;
+use super::dates::DateSpec;
+use super::editing::MessageInput;
+use super::history::MAX_UPCOMING_DAYS;
+use super::search::{Search, SearchMode};
+use super::shell::Shell;
 
 extern crate getopts;
 
@@ -6,10 +14,29 @@ extern crate getopts;
 pub enum Action {
     Help,
     Morning,
-    Message(i64),
-    Past(i64),
+    Message(DateSpec, MessageInput),
+    Past(DateSpec),
+    List,
+    Edit(u64, MessageInput),
+    Delete(u64),
+    Recur(String, MessageInput),
+    Recurring,
+    Cancel(u64),
+    Search(Search),
+    Restore(u64),
+    RepairHistory,
     Command,
     Reminder,
+    ShellInit(Shell, bool),
+    // With true, run even when the routine already ran today
+    Once(bool),
+    DryRun,
+    // Number of days to list, every future message when missing
+    Upcoming(Option<i64>),
+    // morning reminder list|snooze ID [DATE]|done ID
+    ReminderList,
+    ReminderSnooze(String, DateSpec),
+    ReminderDone(String),
 }
 
 pub struct ArgParser{
@@ -22,15 +49,37 @@ impl ArgParser{
 
         let mut argparser = getopts::Options::new();
         argparser.optflag("h", "help", "Display this help and exit");
-        argparser.optflagopt("n", "next", "Used to write a message for the next day", "DAYS");
-        argparser.optflagopt("p", "past", "Show past messages for number of days", "DAYS");
+        argparser.optflagopt("n", "next", "Used to write a message for the next day or DATE (2026-12-24, friday, next friday, +2w, 3d, 1m)", "DATE");
+        argparser.optflagopt("p", "past", "Show past messages of the previous day or DATE (2026-12-24, last monday, 3, 2w)", "DATE");
+        argparser.optopt("m", "message", "Use TEXT as the message instead of opening the editor, stdin is also read when piped", "TEXT");
+        argparser.optflag("a", "append", "Add the text after the existing message instead of replacing it");
+        argparser.optflag("l", "list", "List all messages with their ID");
+        argparser.optopt("e", "edit", "Edit the message with this ID", "ID");
+        argparser.optopt("d", "delete", "Delete the message with this ID", "ID");
+        argparser.optopt("", "recur", "Write a message repeated following RULE, e.g. FREQ=WEEKLY;BYDAY=MO", "RULE");
+        argparser.optflag("", "recurring", "List recurring messages with their ID");
+        argparser.optopt("", "cancel", "Cancel the recurring message with this ID", "ID");
+        argparser.optopt("s", "search", "Show messages containing PATTERN", "PATTERN");
+        argparser.optflag("i", "ignore-case", "Search without matching case");
+        argparser.optflag("", "regex", "Search using PATTERN as a regular expression");
+        argparser.optopt("", "from", "Only search messages on or after DATE (2026-07-01, 3m)", "DATE");
+        argparser.optopt("", "to", "Only search messages on or before DATE (2026-09-30, +1w)", "DATE");
+        argparser.optflag("", "archived", "Also search messages in the archive");
+        argparser.optopt("", "restore", "Move the archived message with this ID back into the history", "ID");
+        argparser.optflag("", "repair-history", "Check the history file and rewrite it");
+        argparser.optopt("", "shell-init", "Print the morning function for SHELL (bash, zsh, fish) so commands can change its directory and variables, use with eval", "SHELL");
+        argparser.optflag("", "once", "Run the morning routine only if it did not run today, with --shell-init also run it from the prompt");
+        argparser.optflag("", "force", "With --once, run the routine even if it already ran today");
+        argparser.optflagopt("u", "upcoming", "List the messages of the next DAYS, or every future message", "DAYS");
+        argparser.optflag("", "dry-run", "Show what the morning routine would run and print, without running it");
+        argparser.optopt("", "date", "Act as if today was DATE (2026-12-24, friday, +1w), MORNING_DATE does the same", "DATE");
         argparser.optflag("c", "command", "Used to write commands to be executed everyday");
         argparser.optflag("r", "reminder", "Used to write messages to be shown each everyday");
 
-        let brief = format!("Usage: morning [options] ...");
+        let brief = "Usage: morning [options] ...\n       morning reminder list|snooze ID [DATE]|done ID";
 
         ArgParser {
-            help_string: String::from(argparser.usage(&brief)),
+            help_string: argparser.usage(brief),
             arg_parser: argparser,
         }
     }
@@ -39,14 +88,14 @@ impl ArgParser{
         print!("{}", self.help_string);
     }
 
-    pub fn parser(&self) -> Result<Action, getopts::Fail> {
+    pub fn parser(&self) -> Result<Action, Box<dyn Error>> {
 
         let args: Vec<String> = env::args().collect();
         let mut action_to_take: Action = Action::Help;
 
         let matches = match self.arg_parser.parse(&args[1..]) {
             Ok(m) => { m }
-            Err(f) => { return Err(f) }
+            Err(f) => { return Err(Box::new(f)) }
         };
 
         // If help is demanded print and exit
@@ -58,16 +107,32 @@ impl ArgParser{
         let option_array = [matches.opt_present("n"),
                             matches.opt_present("r"),
                             matches.opt_present("p"),
+                            matches.opt_present("l"),
+                            matches.opt_present("e"),
+                            matches.opt_present("d"),
+                            matches.opt_present("recur"),
+                            matches.opt_present("recurring"),
+                            matches.opt_present("cancel"),
+                            matches.opt_present("s"),
+                            matches.opt_present("restore"),
+                            matches.opt_present("repair-history"),
+                            matches.opt_present("shell-init"),
+                            matches.opt_present("dry-run"),
+                            matches.opt_present("u"),
                             matches.opt_present("c")];
-        let mut option_count = 0;
+        let option_count = option_array.iter().filter(|present| **present).count();
 
-        for i in 0..option_array.len() {
-            if option_array[i] == true {
-                option_count += 1;
-            }
-            if option_count > 1 {
-                return Ok(Action::Help);
-            }
+        if option_count > 1 {
+            return Ok(Action::Help);
+        }
+
+        // The text and append options only go with the actions writing a message
+        if matches.opt_present("m") && !(matches.opt_present("n") || matches.opt_present("e") || matches.opt_present("recur")) {
+            return Err("--message needs --next, --edit or --recur".into());
+        }
+
+        if matches.opt_present("a") && !(matches.opt_present("n") || matches.opt_present("e")) {
+            return Err("--append needs --next or --edit".into());
         }
 
         // If no options were provided, use default behavior
@@ -75,27 +140,107 @@ impl ArgParser{
             action_to_take = Action::Morning;
         }
 
-        let mut nb_of_days;
-
         // Check option used
         if matches.opt_present("n") {
-            nb_of_days = match matches.opt_str("n"){
-                // Unwrap panics if not an i64 so should match for error use ?
-                Some(nb_days) => nb_days.parse::<i64>().unwrap(),
-                None => 1, 
+            let date = match matches.opt_str("n"){
+                Some(date) => date.parse::<DateSpec>()?,
+                None => "1".parse::<DateSpec>()?,
             };
 
-            action_to_take = Action::Message(nb_of_days);
+            action_to_take = Action::Message(date, MessageInput::new(matches.opt_str("m"), matches.opt_present("a")));
         }
 
         if matches.opt_present("p") {
-            nb_of_days = match matches.opt_str("p"){
-                // Unwrap panics if not an i64 so should match for error use ?
-                Some(nb_days) => nb_days.parse::<i64>().unwrap(),
-                None => 1, 
+            let date = match matches.opt_str("p"){
+                Some(date) => date.parse::<DateSpec>()?,
+                None => "1".parse::<DateSpec>()?,
+            };
+
+            action_to_take = Action::Past(date);
+        }
+
+        if matches.opt_present("l") {
+            action_to_take = Action::List;
+        }
+
+        if let Some(id) = matches.opt_str("e") {
+            action_to_take = Action::Edit(parse_id(&id)?, MessageInput::new(matches.opt_str("m"), matches.opt_present("a")));
+        }
+
+        if let Some(id) = matches.opt_str("d") {
+            action_to_take = Action::Delete(parse_id(&id)?);
+        }
+
+        if let Some(rule) = matches.opt_str("recur") {
+            action_to_take = Action::Recur(rule, MessageInput::new(matches.opt_str("m"), false));
+        }
+
+        if matches.opt_present("recurring") {
+            action_to_take = Action::Recurring;
+        }
+
+        if let Some(id) = matches.opt_str("cancel") {
+            action_to_take = Action::Cancel(parse_id(&id)?);
+        }
+
+        if let Some(mut pattern) = matches.opt_str("s") {
+            if matches.opt_present("regex") && matches.opt_present("i") {
+                pattern = format!("(?i){}", pattern);
+            }
+
+            let mode = if matches.opt_present("regex") {
+                SearchMode::Regex
+            } else if matches.opt_present("i") {
+                SearchMode::IgnoreCase
+            } else {
+                SearchMode::Plain
+            };
+
+            let from = match matches.opt_str("from") {
+                Some(date) => Some(date.parse::<DateSpec>()?),
+                None => None,
+            };
+
+            let to = match matches.opt_str("to") {
+                Some(date) => Some(date.parse::<DateSpec>()?),
+                None => None,
             };
 
-            action_to_take = Action::Past(nb_of_days);
+            action_to_take = Action::Search(Search::new(&pattern, mode, from, to, matches.opt_present("archived"))?);
+        }
+
+        if let Some(id) = matches.opt_str("restore") {
+            action_to_take = Action::Restore(parse_id(&id)?);
+        }
+
+        if matches.opt_present("repair-history") {
+            action_to_take = Action::RepairHistory;
+        }
+
+        if matches.opt_present("once") && option_count == 0 {
+            action_to_take = Action::Once(matches.opt_present("force"));
+        }
+
+        // Words left after the options
+        let mut free = matches.free.clone();
+
+        if matches.opt_present("u") {
+            let days = match matches.opt_str("u") {
+                Some(days) => Some(parse_days(&days)?),
+                // --upcoming 10 leaves the number with the words
+                None if free.len() == 1 && free[0].parse::<u32>().is_ok() => Some(parse_days(&free.remove(0))?),
+                None => None,
+            };
+
+            action_to_take = Action::Upcoming(days);
+        }
+
+        if matches.opt_present("dry-run") {
+            action_to_take = Action::DryRun;
+        }
+
+        if let Some(shell) = matches.opt_str("shell-init") {
+            action_to_take = Action::ShellInit(shell.parse::<Shell>()?, matches.opt_present("once"));
         }
 
         if matches.opt_present("r") {
@@ -106,12 +251,62 @@ impl ArgParser{
             action_to_take = Action::Command;
         }
 
+        // Reminder commands are given as words
+        if matches.free.first().is_some_and(|word| word == "reminder") && option_count == 0 {
+            return parse_reminder_command(&matches.free[1..]);
+        }
+
         // Checks if other arguments were not parsed
-        if !matches.free.is_empty() {
-            println!("Extra arguments were found : {}", matches.free[0].clone());
+        if !free.is_empty() {
+            println!("Extra arguments were found : {}", free[0].clone());
             action_to_take = Action::Help;
         }
 
         return Ok(action_to_take);
     }
+
+    // Date of the morning set with --date, or else with MORNING_DATE
+    pub fn date(&self) -> Result<Option<DateSpec>, Box<dyn Error>> {
+
+        let args: Vec<String> = env::args().collect();
+
+        // Bad arguments are reported by parser
+        let date = match self.arg_parser.parse(&args[1..]) {
+            Ok(matches) => matches.opt_str("date").or_else(clock::date_variable),
+            Err(_) => None,
+        };
+
+        match date {
+            Some(date) => Ok(Some(date.parse::<DateSpec>()?)),
+            None => Ok(None),
+        }
+    }
+}
+
+fn parse_reminder_command(words: &[String]) -> Result<Action, Box<dyn Error>> {
+
+    let words: Vec<&str> = words.iter().map(|word| word.as_str()).collect();
+
+    match words.as_slice() {
+        ["list"] => Ok(Action::ReminderList),
+        ["snooze", id] => Ok(Action::ReminderSnooze(String::from(*id), "1d".parse::<DateSpec>()?)),
+        ["snooze", id, date] => Ok(Action::ReminderSnooze(String::from(*id), date.parse::<DateSpec>()?)),
+        ["done", id] => Ok(Action::ReminderDone(String::from(*id))),
+        _ => Err("Use morning reminder list, morning reminder snooze ID [DATE] or morning reminder done ID".into()),
+    }
+}
+
+fn parse_days(days: &str) -> Result<i64, Box<dyn Error>> {
+    match days.parse::<u32>() {
+        Ok(days) if days as i64 > MAX_UPCOMING_DAYS => Err(format!("Cannot look more than {} days ahead", MAX_UPCOMING_DAYS).into()),
+        Ok(days) => Ok(days as i64),
+        Err(_) => Err(format!("Invalid number of days : {}", days).into()),
+    }
+}
+
+fn parse_id(id: &str) -> Result<u64, Box<dyn Error>> {
+    match id.parse::<u64>() {
+        Ok(id) => Ok(id),
+        Err(_) => Err(format!("Invalid message ID : {}", id).into()),
+    }
 }
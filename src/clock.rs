@@ -0,0 +1,95 @@
+extern crate chrono_tz;
+
+use std::env;
+
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+
+use super::configuration::Configuration;
+
+// Overrides the date of the morning, same as --date
+const DATE_VARIABLE: &str = "MORNING_DATE";
+
+// Where the date of the morning comes from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Clock {
+    // Real time in the timezone, or the system one, shifted by day_start_hour
+    System(Option<Tz>, u32),
+    // A date given with --date or MORNING_DATE, the time of day stays the real one
+    Fixed(NaiveDate),
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock::System(None, 0)
+    }
+}
+
+impl Clock {
+
+    // The clock of the configured timezone and day_start_hour
+    pub fn system(conf: &Configuration) -> Self {
+
+        let timezone = match conf.timezone() {
+            Some(name) => {
+                match name.parse::<Tz>() {
+                    Ok(timezone) => Some(timezone),
+                    Err(_) => {
+                        println!("Unknown timezone {}, using the system timezone", name);
+                        None
+                    }
+                }
+            },
+            None => None,
+        };
+
+        Clock::System(timezone, conf.day_start_hour())
+    }
+
+    pub fn now(&self) -> NaiveDateTime {
+        match self {
+            Clock::System(Some(timezone), _) => Utc::now().with_timezone(timezone).naive_local(),
+            Clock::System(None, _) => Local::now().naive_local(),
+            Clock::Fixed(date) => date.and_time(Local::now().time()),
+        }
+    }
+
+    // The date of the morning
+    // Before day_start_hour the previous day is still considered today
+    pub fn today(&self) -> NaiveDate {
+        match self {
+            Clock::System(_, day_start_hour) => (self.now() - Duration::hours(*day_start_hour as i64)).date(),
+            Clock::Fixed(date) => *date,
+        }
+    }
+
+    pub fn is_fixed(&self) -> bool {
+        matches!(self, Clock::Fixed(_))
+    }
+}
+
+// Date given in MORNING_DATE, in any format --date accepts
+pub fn date_variable() -> Option<String> {
+    env::var(DATE_VARIABLE).ok().filter(|date| !date.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_clock_gives_its_date() {
+        let date = NaiveDate::from_ymd_opt(2026, 2, 28).unwrap();
+        let clock = Clock::Fixed(date);
+
+        assert!(clock.is_fixed());
+        assert_eq!(clock.today(), date);
+        assert_eq!(clock.now().date(), date);
+    }
+
+    #[test]
+    fn system_clock_is_not_fixed() {
+        assert!(!Clock::default().is_fixed());
+        assert!(!Clock::System(Some(chrono_tz::Europe::Paris), 4).is_fixed());
+    }
+}
@@ -1,27 +1,185 @@
+use std::env;
 use std::fs;
-use std::process::Command;
+use std::process::{Child, Command, ExitStatus, Stdio};
 use std::io;
-use std::io::Write;
-use std::io::ErrorKind;
+use std::io::prelude::*;
+use std::io::{ErrorKind, IsTerminal};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use super::configuration::Configuration;
+use chrono::NaiveDate;
+
+use super::configuration::{Configuration, CommandOutput, OutputOrder};
+use super::command_list::{self, CommandDefinition};
 use super::editing;
+use super::pty;
+use super::shell;
+
+// How often a command with a timeout is checked
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// Result of one command once it is finished or stopped
+struct Outcome {
+    status:    Option<ExitStatus>,
+    timed_out: bool,
+    duration:  Duration,
+    // Only filled when the output is captured instead of shown
+    output:    Vec<u8>,
+}
+
+enum Status {
+    Finished(Outcome),
+    Error(String),
+    Skipped(String),
+}
+
+// What is shown for a command of the list once it is done
+struct Report {
+    status:   Status,
+    attempts: u32,
+}
+
+impl Report {
+    fn new(status: Status) -> Self {
+        Report {
+            status:   status,
+            attempts: 1,
+        }
+    }
+
+
+    fn success(&self) -> bool {
+        match &self.status {
+            Status::Finished(outcome) => !outcome.timed_out && outcome.status.is_some_and(|s| s.success()),
+            _ => false,
+        }
+    }
+
+    fn print(&self, name: &str, show_output: bool) {
+
+        println!("* {}", name);
+
+        if let Status::Finished(outcome) = &self.status {
+            if show_output && !outcome.output.is_empty() {
+                let mut stdout = io::stdout();
+                let _ = stdout.write_all(&outcome.output);
+                if !outcome.output.ends_with(b"\n") {
+                    println!();
+                }
+            }
+        }
+
+        self.print_status();
+    }
+
+    fn print_status(&self) {
+        println!("  {}", self.describe());
+    }
+
+    fn describe(&self) -> String {
+        match &self.status {
+            Status::Finished(outcome) if self.attempts > 1 => format!("{}, {} attempts", outcome.describe(), self.attempts),
+            Status::Finished(outcome) => outcome.describe(),
+            Status::Error(error) => error.clone(),
+            Status::Skipped(reason) => format!("skipped, {}", reason),
+        }
+    }
+}
+
+enum State {
+    Pending,
+    Running,
+    Done(Report),
+}
 
-pub fn execute_commands(conf: &Configuration){
+impl Outcome {
+    fn describe(&self) -> String {
+        let seconds = self.duration.as_secs_f64();
+
+        if self.timed_out {
+            return format!("timed out after {:.2}s", seconds);
+        }
+
+        match self.status {
+            Some(status) if status.success() => format!("done in {:.2}s", seconds),
+            Some(status) => format!("failed with {} after {:.2}s", status, seconds),
+            None => format!("stopped after {:.2}s", seconds),
+        }
+    }
+}
+
+// The commands of the list only run when their conditions hold on today
+pub fn execute_commands(conf: &Configuration, today: NaiveDate){
 
     let command_string = fs::read_to_string(conf.command_file());
 
+    let mut header_printed = false;
+    let mut print_header = || {
+        if !header_printed {
+            println!("------------------- Commands --------------------");
+            header_printed = true;
+        }
+    };
+
+    // Name and result of everything run, for the summary
+    let mut results: Vec<(String, Report)> = Vec::new();
+
     match command_string {
         Ok(commands) => {
             // Do not print the section if there is nothing to write
             if !commands.is_empty() {
-                println!("------------------- Commands --------------------");
-                let output = Command::new("bash").arg(conf.command_file()).output().expect("Cannot execute commands");
-                io::stdout().write_all(&output.stdout).unwrap();
-                io::stderr().write_all(&output.stderr).unwrap();
+                print_header();
+
+                let interpreter = interpreter(&commands, conf);
+
+                let mut command = Command::new(&interpreter[0]);
+                command.args(&interpreter[1..]);
+
+                // Sourced, so the changes it makes can be given back to the shell of the wrapper
+                let capture = shell::capture_path(&interpreter);
+
+                match &capture {
+                    Some(capture) => {
+                        command.arg("-c")
+                               .arg(shell::capture_script())
+                               .arg(conf.command_file())
+                               .arg(capture);
+                    },
+                    None => {
+                        command.arg(conf.command_file());
+                    }
+                }
+
+                let timeout = conf.command_timeout().map(Duration::from_secs);
+                let name = conf.command_file().file_name().map_or(String::from("command"), |n| n.to_string_lossy().to_string());
 
-                // Source does not exist it is part of the shell, will have to find something
-                // std::os::unix::process::parent_id -> u32
+                match run(command, conf, timeout, false) {
+                    Ok(outcome) => {
+                        if outcome.status.is_none_or(|status| !status.success()) {
+                            println!("Commands {}", outcome.describe());
+                        }
+                        results.push((name, Report::new(Status::Finished(outcome))));
+                    },
+                    Err(e) if e.kind() == ErrorKind::NotFound => {
+                        println!("Cannot execute commands, the interpreter {} was not found", interpreter[0]);
+                        println!("Change the shebang of {} or the shell setting", conf.command_file().display());
+                        results.push((name, Report::new(Status::Error(e.to_string()))));
+                    },
+                    Err(e) => {
+                        println!("Cannot execute commands");
+                        println!("{}", e);
+                        results.push((name, Report::new(Status::Error(e.to_string()))));
+                    }
+                }
+
+                if let Some(capture) = capture {
+                    if let Err(e) = shell::apply_capture(&capture) {
+                        println!("Cannot give the changes of the commands back to the shell");
+                        println!("{}", e);
+                    }
+                }
             }
         },
         Err(e) => {
@@ -30,20 +188,562 @@ pub fn execute_commands(conf: &Configuration){
                     // No file is present, so just ignore this section
                 },
                 ErrorKind::PermissionDenied => {
-                    println!("------------------- Commands --------------------");
-                    println!("Cannot read the file {}, permission denied", 
+                    print_header();
+                    println!("Cannot read the file {}, permission denied",
                              conf.command_file().display());
                 }
                 _ => {
-                    println!("------------------- Commands --------------------");
+                    print_header();
                     println!("Cannot read command file");
                     println!("{}", e);
                 }
             }
         }
     }
+
+    match command_list::load_command_list(conf) {
+        Ok(definitions) => {
+            let definitions: Vec<&CommandDefinition> = definitions.iter()
+                                                                  .filter(|d| d.enabled && d.when.unmet(today).is_none())
+                                                                  .collect();
+
+            if !definitions.is_empty() {
+                print_header();
+                let reports = run_definitions(&definitions, conf);
+                results.extend(definitions.iter().map(|d| d.name.clone()).zip(reports));
+            }
+        },
+        Err(e) => {
+            print_header();
+            println!("Cannot read {}", command_list::command_list_path(conf).display());
+            println!("{}", e);
+        }
+    }
+
+    if !results.is_empty() {
+        print_summary(&results);
+    }
+}
+
+// What execute_commands would run, without running anything
+pub fn preview_commands(conf: &Configuration, today: NaiveDate) {
+
+    println!("------------------- Commands --------------------");
+
+    let current_dir = env::current_dir().map_or(String::from("the current directory"), |dir| dir.display().to_string());
+    let mut nothing_to_run = true;
+
+    match fs::read_to_string(conf.command_file()) {
+        Ok(commands) => {
+            if !commands.is_empty() {
+                println!("* {}", conf.command_file().display());
+                println!("  with {}", interpreter(&commands, conf).join(" "));
+                println!("  in {}", current_dir);
+                nothing_to_run = false;
+            }
+        },
+        Err(e) => {
+            if e.kind() != ErrorKind::NotFound {
+                println!("Cannot read command file");
+                println!("{}", e);
+            }
+        }
+    }
+
+    match command_list::load_command_list(conf) {
+        Ok(definitions) => {
+            for definition in &definitions {
+                if !definition.enabled {
+                    println!("* {} (disabled)", definition.name);
+                    continue;
+                }
+
+                if let Some(reason) = definition.when.unmet(today) {
+                    println!("* {} (not today, {})", definition.name, reason);
+                    continue;
+                }
+
+                println!("* {}", definition.name);
+                for line in definition.command.lines() {
+                    println!("    {}", line);
+                }
+                println!("  with {} -c", definition.shell);
+
+                match definition.working_dir() {
+                    Some(cwd) if !cwd.is_dir() => println!("  in {}, which does not exist", cwd.display()),
+                    Some(cwd) => println!("  in {}", cwd.display()),
+                    None => println!("  in {}", current_dir),
+                }
+
+                if let Some(timeout) = definition.timeout.or(conf.command_timeout()) {
+                    println!("  timeout {}s", timeout);
+                }
+                if definition.retries > 0 {
+                    println!("  {} retries, {}s apart then doubling", definition.retries, definition.retry_delay);
+                }
+                if !definition.depends_on.is_empty() {
+                    println!("  after {}", definition.depends_on.join(", "));
+                }
+
+                nothing_to_run = false;
+            }
+
+            if conf.command_concurrency() > 1 && definitions.len() > 1 {
+                println!("Up to {} commands at the same time", conf.command_concurrency());
+            }
+        },
+        Err(e) => {
+            println!("Cannot read {}", command_list::command_list_path(conf).display());
+            println!("{}", e);
+        }
+    }
+
+    if nothing_to_run {
+        println!("No command to run");
+    }
+}
+
+// Program and arguments to run the command file with
+// A shebang comes first, then the shell setting, then bash
+fn interpreter(commands: &str, conf: &Configuration) -> Vec<String> {
+
+    if let Some(shebang) = commands.lines().next().and_then(|line| line.strip_prefix("#!")) {
+        let words: Vec<String> = shebang.split_whitespace().map(String::from).collect();
+        if !words.is_empty() {
+            return words;
+        }
+    }
+
+    if !conf.shell().is_empty() {
+        return conf.shell().to_vec();
+    }
+
+    return vec![String::from("bash")];
+}
+
+// One line per kind of result, with the exit code of the failures
+fn print_summary(results: &[(String, Report)]) {
+
+    let mut succeeded: Vec<String> = Vec::new();
+    let mut failed:    Vec<String> = Vec::new();
+    let mut timed_out: Vec<String> = Vec::new();
+    let mut skipped:   Vec<String> = Vec::new();
+
+    for (name, report) in results {
+        match &report.status {
+            Status::Finished(outcome) if outcome.timed_out => timed_out.push(name.clone()),
+            Status::Finished(outcome) => {
+                match outcome.status {
+                    Some(status) if status.success() => succeeded.push(name.clone()),
+                    Some(status) => failed.push(format!("{} ({})", name, exit_code(status))),
+                    None => failed.push(format!("{} (stopped)", name)),
+                }
+            },
+            Status::Error(_) => failed.push(format!("{} (not started)", name)),
+            Status::Skipped(_) => skipped.push(name.clone()),
+        }
+    }
+
+    println!();
+
+    for (label, names) in [("Succeeded", succeeded), ("Failed", failed), ("Timed out", timed_out), ("Skipped", skipped)] {
+        if !names.is_empty() {
+            println!("{:<9} : {}", label, names.join(", "));
+        }
+    }
+}
+
+fn exit_code(status: ExitStatus) -> String {
+    match (status.code(), status.signal()) {
+        (Some(code), _) => format!("exit code {}", code),
+        (None, Some(signal)) => format!("signal {}", signal),
+        (None, None) => String::from("unknown status"),
+    }
+}
+
+// Runs the list following depends_on, up to command_concurrency at a time
+// One at a time, the output is shown live, otherwise it is kept and shown in order
+fn run_definitions(definitions: &[&CommandDefinition], conf: &Configuration) -> Vec<Report> {
+
+    let concurrency = conf.command_concurrency();
+    let live = concurrency == 1;
+    let order = conf.command_output_order();
+
+    let mut states: Vec<State> = definitions.iter().map(|_| State::Pending).collect();
+    let mut running = 0;
+    let mut next_to_print = 0;
+
+    let (sender, receiver) = mpsc::channel::<(usize, Report)>();
+
+    thread::scope(|scope| {
+        loop {
+            // Skip the commands that can never start
+            for i in 0..definitions.len() {
+                if let State::Pending = states[i] {
+                    if let Some(reason) = blocked_reason(definitions[i], definitions, &states) {
+                        states[i] = State::Done(Report::new(Status::Skipped(reason)));
+                        if live || order == OutputOrder::Completion {
+                            if let State::Done(report) = &states[i] {
+                                report.print(&definitions[i].name, false);
+                            }
+                        }
+                    }
+                }
+            }
+
+            // Start every command ready to go, in the declared order
+            let mut started = false;
+
+            for i in 0..definitions.len() {
+                if running >= concurrency {
+                    break;
+                }
+
+                if let State::Pending = states[i] {
+                    if !dependencies_done(definitions[i], definitions, &states) {
+                        continue;
+                    }
+
+                    if live {
+                        println!("* {}", definitions[i].name);
+                        let report = run_definition(definitions[i], conf, false);
+                        report.print_status();
+                        states[i] = State::Done(report);
+                        started = true;
+                        // Its result may skip or unblock other commands
+                        break;
+                    } else {
+                        let definition = definitions[i];
+                        let sender = sender.clone();
+                        scope.spawn(move || {
+                            let _ = sender.send((i, run_definition(definition, conf, true)));
+                        });
+                        states[i] = State::Running;
+                        running += 1;
+                        started = true;
+                    }
+                }
+            }
+
+            // Nothing can start and nothing runs, whatever is left waits on itself
+            if running == 0 && !started {
+                for i in 0..definitions.len() {
+                    if let State::Pending = states[i] {
+                        states[i] = State::Done(Report::new(Status::Skipped(String::from("circular depends_on"))));
+                        if live || order == OutputOrder::Completion {
+                            if let State::Done(report) = &states[i] {
+                                report.print(&definitions[i].name, false);
+                            }
+                        }
+                    }
+                }
+            }
+
+            if running > 0 {
+                if let Ok((i, report)) = receiver.recv() {
+                    running -= 1;
+                    if order == OutputOrder::Completion {
+                        report.print(&definitions[i].name, true);
+                    }
+                    states[i] = State::Done(report);
+                }
+            }
+
+            if !live && order == OutputOrder::Declared {
+                while next_to_print < definitions.len() {
+                    match &states[next_to_print] {
+                        State::Done(report) => report.print(&definitions[next_to_print].name, true),
+                        _ => break,
+                    }
+                    next_to_print += 1;
+                }
+            }
+
+            let pending = states.iter().any(|s| matches!(s, State::Pending));
+            if running == 0 && !pending {
+                break;
+            }
+        }
+    });
+
+    states.into_iter().filter_map(|state| {
+        match state {
+            State::Done(report) => Some(report),
+            _ => None,
+        }
+    }).collect()
+}
+
+fn find_definition(name: &str, definitions: &[&CommandDefinition]) -> Option<usize> {
+    definitions.iter().position(|d| d.name == name)
+}
+
+fn dependencies_done(definition: &CommandDefinition, definitions: &[&CommandDefinition], states: &[State]) -> bool {
+    definition.depends_on.iter().all(|name| {
+        match find_definition(name, definitions) {
+            Some(index) => matches!(&states[index], State::Done(report) if report.success()),
+            None => false,
+        }
+    })
+}
+
+// Why a command cannot start anymore, if it is the case
+fn blocked_reason(definition: &CommandDefinition, definitions: &[&CommandDefinition], states: &[State]) -> Option<String> {
+    for name in &definition.depends_on {
+        match find_definition(name, definitions) {
+            Some(index) => {
+                if let State::Done(report) = &states[index] {
+                    if !report.success() {
+                        return Some(format!("{} did not succeed", name));
+                    }
+                }
+            },
+            None => {
+                return Some(format!("{} is unknown or does not run today", name));
+            }
+        }
+    }
+
+    None
+}
+
+// Runs it again after a failure as long as there are retries left
+fn run_definition(definition: &CommandDefinition, conf: &Configuration, capture: bool) -> Report {
+
+    let timeout = definition.timeout.or(conf.command_timeout()).map(Duration::from_secs);
+    let mut output: Vec<u8> = Vec::new();
+    let mut attempt = 1;
+
+    loop {
+        let mut report = run_attempt(definition, conf, timeout, capture);
+        report.attempts = attempt;
+
+        if let Status::Finished(outcome) = &mut report.status {
+            output.append(&mut outcome.output);
+        }
+
+        // Only a command that ran can do better the next time
+        let retry = matches!(report.status, Status::Finished(_)) && !report.success() && attempt <= definition.retries;
+
+        if !retry {
+            if let Status::Finished(outcome) = &mut report.status {
+                outcome.output = output;
+            }
+            return report;
+        }
+
+        let delay = definition.retry_delay.saturating_mul(1 << (attempt - 1).min(16));
+        let line = format!("  {}, retrying in {}s\n", report.describe(), delay);
+
+        if capture {
+            output.extend_from_slice(line.as_bytes());
+        } else {
+            print!("{}", line);
+        }
+
+        thread::sleep(Duration::from_secs(delay));
+        attempt += 1;
+    }
+}
+
+fn run_attempt(definition: &CommandDefinition, conf: &Configuration, timeout: Option<Duration>, capture: bool) -> Report {
+
+    let mut command = Command::new(&definition.shell);
+    command.arg("-c")
+           .arg(&definition.command)
+           .envs(&definition.env);
+
+    if let Some(cwd) = definition.working_dir() {
+        if !cwd.is_dir() {
+            return Report::new(Status::Error(format!("Working directory {} does not exist", cwd.display())));
+        }
+        command.current_dir(cwd);
+    }
+
+    match run(command, conf, timeout, capture) {
+        Ok(outcome) => {
+            Report::new(Status::Finished(outcome))
+        },
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            Report::new(Status::Error(format!("Cannot execute {}, the shell {} was not found", definition.name, definition.shell)))
+        },
+        Err(e) => {
+            Report::new(Status::Error(format!("Cannot execute {} : {}", definition.name, e)))
+        }
+    }
+}
+
+// Output is shown as it comes, with stdout and stderr in their original order
+// With capture it is kept in the outcome instead, and the command cannot use the terminal
+fn run(mut command: Command, conf: &Configuration, timeout: Option<Duration>, capture: bool) -> Result<Outcome, io::Error> {
+
+    let interactive = conf.interactive_commands() && !capture;
+    let start = Instant::now();
+
+    let mut mode = conf.command_output();
+
+    // A terminal is only useful when morning itself writes to one
+    if mode == CommandOutput::Pty && !io::stdout().is_terminal() {
+        mode = CommandOutput::Pipe;
+    }
+
+    // Captured output cannot go straight to the terminal
+    if mode == CommandOutput::Inherit && capture {
+        mode = CommandOutput::Pipe;
+    }
+
+    // In its own process group, a timeout stops everything the command started
+    // The pseudo-terminal always gives it its own session, which is also a process group
+    let group = mode == CommandOutput::Pty || timeout.is_some();
+
+    // A background group reading the terminal is stopped, so the group is given the terminal
+    let foreground = group && mode != CommandOutput::Pty && io::stdin().is_terminal();
+
+    if group && mode != CommandOutput::Pty {
+        command.process_group(0);
+    }
+
+    let (mut child, output, raw_terminal): (Child, Option<Box<dyn Read + Send>>, Option<pty::RawTerminal>) = match mode {
+        CommandOutput::Pty => {
+            let (child, master, raw_terminal) = pty::spawn(command, interactive)?;
+            (child, Some(Box::new(master)), raw_terminal)
+        },
+        CommandOutput::Pipe => {
+            // Stdout and stderr share one pipe so they keep their order
+            let (reader, writer) = io::pipe()?;
+
+            command.stdin(stdin_for(interactive))
+                   .stdout(writer.try_clone()?)
+                   .stderr(writer);
+
+            let child = command.spawn()?;
+
+            // The command keeps its end of the pipe open until it is dropped
+            drop(command);
+
+            (child, Some(Box::new(reader)), None)
+        },
+        CommandOutput::Inherit => {
+            command.stdin(stdin_for(interactive));
+            (command.spawn()?, None, None)
+        }
+    };
+
+    let copier = output.map(|output| thread::spawn(move || copy_output(output, capture)));
+
+    if foreground {
+        give_terminal(child.id() as libc::pid_t);
+    }
+
+    let waited = wait_with_timeout(&mut child, timeout, group);
+
+    if foreground {
+        give_terminal(unsafe { libc::getpgrp() });
+    }
+
+    let (status, timed_out) = waited?;
+
+    let mut captured: Vec<u8> = Vec::new();
+
+    if let Some(copier) = copier {
+        if let Ok(result) = copier.join() {
+            captured = result?;
+        }
+    }
+
+    drop(raw_terminal);
+
+    Ok(
+        Outcome {
+            status:    status,
+            timed_out: timed_out,
+            duration:  start.elapsed(),
+            output:    captured,
+        }
+    )
+}
+
+// Makes the group the foreground one of the terminal
+// The command may already be stopped for reading it, so it is told to continue
+fn give_terminal(group: libc::pid_t) {
+    unsafe {
+        // Changing it from a background group would stop morning otherwise
+        let previous = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+        libc::tcsetpgrp(libc::STDIN_FILENO, group);
+        libc::signal(libc::SIGTTOU, previous);
+
+        if group != libc::getpgrp() {
+            libc::kill(-group, libc::SIGCONT);
+        }
+    }
+}
+
+fn stdin_for(interactive: bool) -> Stdio {
+    if interactive {
+        Stdio::inherit()
+    } else {
+        Stdio::null()
+    }
+}
+
+fn wait_with_timeout(child: &mut Child, timeout: Option<Duration>, group: bool) -> Result<(Option<ExitStatus>, bool), io::Error> {
+
+    let deadline = match timeout {
+        Some(timeout) => Instant::now() + timeout,
+        None => return Ok((Some(child.wait()?), false)),
+    };
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok((Some(status), false));
+        }
+
+        if Instant::now() >= deadline {
+            if group {
+                // The group id is the id of the command
+                unsafe {
+                    libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+                }
+            } else {
+                child.kill()?;
+            }
+            child.wait()?;
+            return Ok((None, true));
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+// Each chunk is flushed so prompts without a newline are visible
+// With capture, the output is returned instead
+fn copy_output(mut output: Box<dyn Read + Send>, capture: bool) -> Result<Vec<u8>, io::Error> {
+
+    let mut captured: Vec<u8> = Vec::new();
+    let mut stdout = io::stdout();
+    let mut buffer = [0; 4096];
+
+    loop {
+        let count = match output.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(count) => count,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            // A pseudo-terminal reports EIO once the command is done
+            Err(ref e) if e.raw_os_error() == Some(libc::EIO) => break,
+            Err(e) => return Err(e),
+        };
+
+        if capture {
+            captured.extend_from_slice(&buffer[..count]);
+        } else {
+            stdout.write_all(&buffer[..count])?;
+            stdout.flush()?;
+        }
+    }
+
+    Ok(captured)
 }
 
 pub fn edit_command(conf: &Configuration) {
     editing::edit_file(conf.command_file());
-}
\ No newline at end of file
+}
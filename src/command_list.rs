@@ -0,0 +1,90 @@
+extern crate toml;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+
+use super::condition::Condition;
+use super::configuration::Configuration;
+use super::storage;
+
+const COMMAND_LIST_FILE: &str = "commands.toml";
+
+// One command of the morning, run on its own with its own status
+// In commands.toml each one is a [[command]] table
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CommandDefinition {
+    pub name:    String,
+    pub command: String,
+    #[serde(default = "default_shell")]
+    pub shell:   String,
+    #[serde(default)]
+    pub cwd:     Option<PathBuf>,
+    #[serde(default)]
+    pub env:     BTreeMap<String, String>,
+    // Seconds before the command is stopped
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // Attempts after a failure or a timeout, waiting retry_delay seconds doubled each time
+    #[serde(default)]
+    pub retries: u32,
+    #[serde(default = "default_retry_delay")]
+    pub retry_delay: u64,
+    // Names of the commands that must succeed before this one starts
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    // Weekdays, dates, hosts... the command only runs when they hold
+    #[serde(flatten)]
+    pub when:    Condition,
+}
+
+#[derive(Deserialize)]
+struct CommandListFile {
+    #[serde(default)]
+    command: Vec<CommandDefinition>,
+}
+
+impl CommandDefinition {
+
+    // A leading ~ is the home directory
+    pub fn working_dir(&self) -> Option<PathBuf> {
+        self.cwd.as_ref().map(|cwd| storage::expand_home(cwd))
+    }
+}
+
+fn default_shell() -> String {
+    String::from("bash")
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_retry_delay() -> u64 {
+    1
+}
+
+// Path of commands.toml, next to the command file
+pub fn command_list_path(conf: &Configuration) -> PathBuf {
+    conf.command_file().with_file_name(COMMAND_LIST_FILE)
+}
+
+// Commands of the configuration followed by the ones of commands.toml
+pub fn load_command_list(conf: &Configuration) -> Result<Vec<CommandDefinition>, Box<dyn Error>> {
+
+    let mut commands: Vec<CommandDefinition> = conf.commands().to_vec();
+
+    let path = command_list_path(conf);
+
+    if fs::metadata(&path).is_ok() {
+        let toml_string = fs::read_to_string(&path)?;
+        let list: CommandListFile = toml::from_str(&toml_string)?;
+        commands.extend(list.command);
+    }
+
+    Ok(commands)
+}
@@ -0,0 +1,124 @@
+extern crate libc;
+
+use std::env;
+use std::ffi::CStr;
+use std::path::PathBuf;
+
+use chrono::{Datelike, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+
+use super::date_format;
+use super::storage;
+
+// Weekdays are read as mon, monday, Mon... and written in their short form
+mod weekday_list_format {
+    use chrono::Weekday;
+    use serde::{self, Deserialize, Serializer, Deserializer};
+
+    pub fn serialize<S>(weekdays: &[Weekday], serializer: S) -> Result<S::Ok, S::Error> where S: Serializer, {
+        serializer.collect_seq(weekdays.iter().map(|weekday| weekday.to_string().to_lowercase()))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D,) -> Result<Vec<Weekday>, D::Error> where D: Deserializer<'de>, {
+        let names: Vec<String> = Vec::deserialize(deserializer)?;
+        names.iter()
+             .map(|name| name.parse::<Weekday>().map_err(|_| serde::de::Error::custom(format!("Invalid weekday : {}", name))))
+             .collect()
+    }
+}
+
+// When a command runs or a reminder is shown, every condition set must hold
+// Added to commands and reminders with serde flatten
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Condition {
+    #[serde(default, with = "weekday_list_format", skip_serializing_if = "Vec::is_empty")]
+    pub weekdays:    Vec<Weekday>,
+    // First and last day, both included
+    #[serde(default, alias = "start", with = "date_format::optional_date", skip_serializing_if = "Option::is_none")]
+    pub from:        Option<NaiveDate>,
+    #[serde(default, alias = "end", with = "date_format::optional_date", skip_serializing_if = "Option::is_none")]
+    pub until:       Option<NaiveDate>,
+    // Host names, the short name before the first dot also matches
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hosts:       Vec<String>,
+    // NAME must be set and not empty, NAME=value must have that value
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub env_set:     Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub path_exists: Vec<PathBuf>,
+}
+
+impl Condition {
+
+    // Why it does not hold on this date, None when it does
+    pub fn unmet(&self, today: NaiveDate) -> Option<String> {
+
+        if !self.weekdays.is_empty() && !self.weekdays.contains(&today.weekday()) {
+            let names: Vec<String> = self.weekdays.iter().map(|weekday| weekday.to_string()).collect();
+            return Some(format!("only on {}", names.join(", ")));
+        }
+
+        if let Some(from) = self.from {
+            if today < from {
+                return Some(format!("only from {}", from));
+            }
+        }
+
+        if let Some(until) = self.until {
+            if today > until {
+                return Some(format!("only until {}", until));
+            }
+        }
+
+        if !self.hosts.is_empty() {
+            let host = hostname().unwrap_or_default().to_lowercase();
+            let short_host = host.split('.').next().unwrap_or_default();
+            let matched = self.hosts.iter().any(|name| {
+                let name = name.to_lowercase();
+                name == host || name == short_host
+            });
+
+            if !matched {
+                return Some(format!("only on {}", self.hosts.join(", ")));
+            }
+        }
+
+        for variable in &self.env_set {
+            let matched = match variable.split_once('=') {
+                Some((name, value)) => env::var(name).is_ok_and(|current| current == value),
+                None => env::var(variable).is_ok_and(|current| !current.is_empty()),
+            };
+
+            if !matched {
+                return Some(format!("only when {} is set", variable));
+            }
+        }
+
+        for path in &self.path_exists {
+            if !storage::expand_home(path).exists() {
+                return Some(format!("only when {} exists", path.display()));
+            }
+        }
+
+        None
+    }
+}
+
+// Name of this machine, None if the system does not give it
+pub fn hostname() -> Option<String> {
+
+    let mut buffer = [0 as libc::c_char; 256];
+
+    let result = unsafe { libc::gethostname(buffer.as_mut_ptr(), buffer.len()) };
+
+    if result != 0 {
+        return None;
+    }
+
+    // Not always terminated when the name is truncated
+    buffer[buffer.len() - 1] = 0;
+
+    let name = unsafe { CStr::from_ptr(buffer.as_ptr()) };
+
+    Some(name.to_string_lossy().to_string())
+}
@@ -5,6 +5,8 @@ use std::error::Error;
 use std::path::Path;
 use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
+use super::storage;
+use super::command_list::CommandDefinition;
 
 extern crate dirs;
 extern crate serde;
@@ -26,21 +28,83 @@ const PORTABLE_HISTORY:   &str = "history.json";
 
 #[derive(Debug)]
 pub struct Configuration {
-    executing_dir: PathBuf,
-    current_dir:   PathBuf,
-    home_dir:      PathBuf,
     portable:      bool,
     config_change: bool,
     config_file:   PathBuf,
     config:        ConfigurationContent,
 }
 
+// What happens to messages older than history_length
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Retention {
+    #[default]
+    Delete,
+    Archive,
+    Keep,
+}
+
+// How the commands are connected to the terminal
+// With pty, each command gets its own pseudo-terminal and the keys typed are forwarded to it,
+// so sudo and other prompts reading the terminal work, interactive_commands or not
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandOutput {
+    #[default]
+    Pty,
+    Pipe,
+    Inherit,
+}
+
+// When the output of commands running in parallel is shown
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputOrder {
+    #[default]
+    Declared,
+    Completion,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct ConfigurationContent {
     command_file:   PathBuf,
     reminder_file:  PathBuf,
     history_file:   PathBuf,
-    history_length: i64
+    history_length: i64,
+    #[serde(default)]
+    retention:      Retention,
+    #[serde(default)]
+    compress_archive: bool,
+    // IANA name such as America/Toronto, the system timezone is used when missing
+    #[serde(default)]
+    timezone:       Option<String>,
+    #[serde(default)]
+    day_start_hour: u32,
+    // Connect the terminal to the commands so they can prompt
+    #[serde(default)]
+    interactive_commands: bool,
+    #[serde(default)]
+    command_output: CommandOutput,
+    #[serde(default)]
+    commands:       Vec<CommandDefinition>,
+    // Number of commands running at the same time, 1 runs them one after another
+    #[serde(default = "default_concurrency")]
+    command_concurrency: usize,
+    #[serde(default)]
+    command_output_order: OutputOrder,
+    // Seconds before a command without its own timeout is stopped, the command file included
+    #[serde(default)]
+    command_timeout: Option<u64>,
+    // Interpreter and its arguments for the command file without a shebang, bash when empty
+    #[serde(default)]
+    shell:          Vec<String>,
+    // Days shown in the Coming up section of the morning, none when 0
+    #[serde(default)]
+    upcoming_days:  i64,
+}
+
+fn default_concurrency() -> usize {
+    1
 }
 
 impl Configuration {
@@ -48,20 +112,14 @@ impl Configuration {
     pub fn new() -> Result<Self, Box<dyn Error>> {
 
         let executing_dir = find_executing_dir()?;
-        let current_dir   = find_current_dir()?;
         let home_dir      = find_home_dir()?;
         let portable      = is_script_portable();
         let mut config_change = false;
 
-        let mut config_file: PathBuf;
+        let config_file = config_path()?;
 
-        if portable {
-            config_file = PathBuf::from(&executing_dir);
-            config_file.push(PORTABLE_CONFIG);
-        } else {
-            config_file = PathBuf::from(&home_dir);
-            config_file.push(INSTALLED_CONFIG);
-        }
+        // Not while another morning writes it
+        let _lock = storage::lock_config(&config_file)?;
 
         let config: ConfigurationContent;
 
@@ -78,9 +136,6 @@ impl Configuration {
 
         Ok(
             Configuration {
-                executing_dir: executing_dir,
-                current_dir:   current_dir,
-                home_dir:      home_dir,
                 portable:      portable,
                 config_change: config_change,
                 config_file:   config_file,
@@ -98,7 +153,7 @@ impl Configuration {
                 Ok(config)
             },
             Err(e) => {
-                Err(io::Error::new(io::ErrorKind::Other, "Serde Error"))
+                Err(io::Error::other(format!("Serde Error : {}", e)))
             }
         }
     }
@@ -106,8 +161,9 @@ impl Configuration {
     pub fn write_config(&self) -> Result<(), Box<dyn Error>> {
 
         if self.config_change {
+            let _lock = storage::lock_config(&self.config_file)?;
             let json_config = serde_json::to_string(&self.config)?;
-            fs::write(&self.config_file, json_config)?;
+            storage::write_with_backup(&self.config_file, json_config.as_bytes())?;
         }
 
         Ok(())
@@ -133,6 +189,18 @@ impl Configuration {
                 reminder_file:  reminder_file,
                 history_file:   history_file,
                 history_length: 15,
+                retention:      Retention::Delete,
+                compress_archive: false,
+                timezone:       None,
+                day_start_hour: 0,
+                interactive_commands: false,
+                command_output: CommandOutput::Pty,
+                commands:       Vec::new(),
+                command_concurrency: 1,
+                command_output_order: OutputOrder::Declared,
+                command_timeout: None,
+                shell:          Vec::new(),
+                upcoming_days:  0,
             }
 
         } else {
@@ -149,6 +217,18 @@ impl Configuration {
                 reminder_file:  reminder_file,
                 history_file:   history_file,
                 history_length: 15,
+                retention:      Retention::Delete,
+                compress_archive: false,
+                timezone:       None,
+                day_start_hour: 0,
+                interactive_commands: false,
+                command_output: CommandOutput::Pty,
+                commands:       Vec::new(),
+                command_concurrency: 1,
+                command_output_order: OutputOrder::Declared,
+                command_timeout: None,
+                shell:          Vec::new(),
+                upcoming_days:  0,
             }
         }
     }
@@ -165,6 +245,54 @@ impl Configuration {
         return self.config.history_length;
     }
 
+    pub fn retention(&self) -> Retention {
+        self.config.retention
+    }
+
+    pub fn compress_archive(&self) -> bool {
+        self.config.compress_archive
+    }
+
+    pub fn timezone(&self) -> Option<&String> {
+        self.config.timezone.as_ref()
+    }
+
+    pub fn day_start_hour(&self) -> u32 {
+        self.config.day_start_hour.min(23)
+    }
+
+    pub fn interactive_commands(&self) -> bool {
+        self.config.interactive_commands
+    }
+
+    pub fn command_output(&self) -> CommandOutput {
+        self.config.command_output
+    }
+
+    pub fn commands(&self) -> &[CommandDefinition] {
+        &self.config.commands
+    }
+
+    pub fn command_concurrency(&self) -> usize {
+        self.config.command_concurrency.max(1)
+    }
+
+    pub fn command_output_order(&self) -> OutputOrder {
+        self.config.command_output_order
+    }
+
+    pub fn command_timeout(&self) -> Option<u64> {
+        self.config.command_timeout
+    }
+
+    pub fn shell(&self) -> &[String] {
+        &self.config.shell
+    }
+
+    pub fn upcoming_days(&self) -> i64 {
+        self.config.upcoming_days.max(0)
+    }
+
     pub fn command_file(&self) -> &PathBuf {
         return &self.config.command_file;
     }
@@ -174,17 +302,29 @@ impl Configuration {
     }
 }
 
+// Where the configuration is read from
+fn config_path() -> Result<PathBuf, Box<dyn Error>> {
+
+    let mut config_file: PathBuf;
+
+    if is_script_portable() {
+        config_file = find_executing_dir()?;
+        config_file.push(PORTABLE_CONFIG);
+    } else {
+        config_file = find_home_dir()?;
+        config_file.push(INSTALLED_CONFIG);
+    }
+
+    Ok(config_file)
+}
+
 fn is_script_portable() -> bool {
 
     let installed_dir: PathBuf = PathBuf::from(INSTALLED_SCRIPT);
 
     match find_executing_dir() {
         Ok(exd) => {
-            if exd == installed_dir {
-                false
-            } else {
-                true
-            }
+            exd != installed_dir
         },
         Err(_) => {
             true
@@ -205,13 +345,6 @@ fn find_executing_dir() -> Result<PathBuf, Box<dyn Error>> {
     Ok(executing_dir)
 }
 
-fn find_current_dir() -> Result<PathBuf, Box<dyn Error>> {
-    Ok(
-        env::current_dir()?
-            .canonicalize()?
-    )
-}
-
 fn find_home_dir() -> Result<PathBuf, Box<dyn Error>> {
     Ok(
         dirs::home_dir()
@@ -0,0 +1,63 @@
+// Serialize and Deserialize for the NaiveDate type, written as 2026-12-24
+// Used with serde(with = "date_format::...") by every file that stores dates
+
+const FORMAT: &str = "%Y-%m-%d";
+
+pub mod date {
+    use chrono::NaiveDate;
+    use serde::{self, Deserialize, Serializer, Deserializer};
+
+    use super::FORMAT;
+
+    pub fn serialize<S>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer, {
+        let s = format!("{}", date.format(FORMAT));
+        serializer.serialize_str(&s)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D,) -> Result<NaiveDate, D::Error> where D: Deserializer<'de>, {
+        let s = String::deserialize(deserializer)?;
+        NaiveDate::parse_from_str(&s, FORMAT).map_err(serde::de::Error::custom)
+    }
+}
+
+// Null when missing
+pub mod optional_date {
+    use chrono::NaiveDate;
+    use serde::{self, Deserialize, Serializer, Deserializer};
+
+    use super::FORMAT;
+
+    pub fn serialize<S>(date: &Option<NaiveDate>, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer, {
+        match date {
+            Some(date) => serializer.serialize_str(&format!("{}", date.format(FORMAT))),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D,) -> Result<Option<NaiveDate>, D::Error> where D: Deserializer<'de>, {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(s) => NaiveDate::parse_from_str(&s, FORMAT).map(Some).map_err(serde::de::Error::custom),
+            None => Ok(None),
+        }
+    }
+}
+
+// Dates by ID
+pub mod date_map {
+    use std::collections::BTreeMap;
+    use chrono::NaiveDate;
+    use serde::{self, Deserialize, Serializer, Deserializer};
+
+    use super::FORMAT;
+
+    pub fn serialize<S>(dates: &BTreeMap<String, NaiveDate>, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer, {
+        serializer.collect_map(dates.iter().map(|(id, date)| (id, date.format(FORMAT).to_string())))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D,) -> Result<BTreeMap<String, NaiveDate>, D::Error> where D: Deserializer<'de>, {
+        let dates: BTreeMap<String, String> = BTreeMap::deserialize(deserializer)?;
+        dates.into_iter()
+             .map(|(id, date)| NaiveDate::parse_from_str(&date, FORMAT).map(|date| (id, date)).map_err(serde::de::Error::custom))
+             .collect()
+    }
+}
@@ -0,0 +1,238 @@
+use std::fmt;
+use std::str::FromStr;
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+// Dates given on the command line, either absolute or relative to today
+// e.g. 2026-12-24, friday, next friday, last monday, 3, +2w, 3d, 1m, -1y
+
+// Offsets further than this are refused before any date arithmetic
+const MAX_OFFSET: i64 = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Future,
+    Past,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unit {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DateSpec {
+    Absolute(NaiveDate),
+    // The direction is only set when the offset had an explicit sign
+    Offset(i64, Unit, Option<Direction>),
+    Weekday(Weekday, Option<Direction>),
+}
+
+#[derive(Debug)]
+pub struct DateError(String);
+
+impl fmt::Display for DateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid date : {}", self.0)
+    }
+}
+
+impl std::error::Error for DateError {}
+
+impl DateSpec {
+
+    // Unsigned offsets and bare weekdays go in the given direction
+    pub fn resolve(&self, today: NaiveDate, direction: Direction) -> Result<NaiveDate, DateError> {
+
+        match self {
+            DateSpec::Absolute(date) => {
+                Ok(*date)
+            },
+            DateSpec::Offset(amount, unit, sign) => {
+                let amount = match sign.unwrap_or(direction) {
+                    Direction::Future => *amount,
+                    Direction::Past => -*amount,
+                };
+
+                let date = match unit {
+                    Unit::Day   => today.checked_add_signed(Duration::days(amount)),
+                    Unit::Week  => today.checked_add_signed(Duration::weeks(amount)),
+                    Unit::Month => add_months(today, amount),
+                    Unit::Year  => add_months(today, amount * 12),
+                };
+
+                date.ok_or_else(|| DateError(String::from("offset is out of range")))
+            },
+            DateSpec::Weekday(day, modifier) => {
+                let today_index = today.weekday().num_days_from_monday() as i64;
+                let day_index = day.num_days_from_monday() as i64;
+
+                // Never today, always the closest one strictly before or after
+                let days = match modifier.unwrap_or(direction) {
+                    Direction::Future => (day_index - today_index + 6).rem_euclid(7) + 1,
+                    Direction::Past => -((today_index - day_index + 6).rem_euclid(7) + 1),
+                };
+
+                Ok(today + Duration::days(days))
+            }
+        }
+    }
+}
+
+impl FromStr for DateSpec {
+    type Err = DateError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+
+        let lowered = input.trim().to_lowercase();
+        let words: Vec<&str> = lowered.split_whitespace().collect();
+
+        match words.as_slice() {
+            ["today"] => Ok(DateSpec::Offset(0, Unit::Day, None)),
+            ["tomorrow"] => Ok(DateSpec::Offset(1, Unit::Day, Some(Direction::Future))),
+            ["yesterday"] => Ok(DateSpec::Offset(1, Unit::Day, Some(Direction::Past))),
+            ["next", day] => Ok(DateSpec::Weekday(parse_weekday(day, input)?, Some(Direction::Future))),
+            ["last", day] => Ok(DateSpec::Weekday(parse_weekday(day, input)?, Some(Direction::Past))),
+            [word] => {
+                if let Ok(date) = NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+                    return Ok(DateSpec::Absolute(date));
+                }
+
+                if let Ok(day) = word.parse::<Weekday>() {
+                    return Ok(DateSpec::Weekday(day, None));
+                }
+
+                parse_offset(word).ok_or_else(|| DateError(String::from(input)))
+            },
+            _ => Err(DateError(String::from(input))),
+        }
+    }
+}
+
+fn parse_weekday(day: &str, input: &str) -> Result<Weekday, DateError> {
+    day.parse::<Weekday>().map_err(|_| DateError(String::from(input)))
+}
+
+// Offsets are a number with an optional sign and unit, days by default
+fn parse_offset(word: &str) -> Option<DateSpec> {
+
+    let (sign, rest) = match word.chars().next()? {
+        '+' => (Some(Direction::Future), &word[1..]),
+        '-' => (Some(Direction::Past), &word[1..]),
+        _ => (None, word),
+    };
+
+    let digits_end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    if digits_end == 0 {
+        return None;
+    }
+
+    let amount = rest[..digits_end].parse::<i64>().ok()?;
+    if amount > MAX_OFFSET {
+        return None;
+    }
+
+    let unit = match &rest[digits_end..] {
+        "" | "d" | "day" | "days"       => Unit::Day,
+        "w" | "week" | "weeks"          => Unit::Week,
+        "m" | "month" | "months"        => Unit::Month,
+        "y" | "year" | "years"          => Unit::Year,
+        _ => return None,
+    };
+
+    Some(DateSpec::Offset(amount, unit, sign))
+}
+
+// Keeps the day of month when possible, otherwise uses the last day of the month
+fn add_months(date: NaiveDate, months: i64) -> Option<NaiveDate> {
+
+    let index = date.year() as i64 * 12 + date.month0() as i64 + months;
+    let year = index.div_euclid(12);
+    let month = index.rem_euclid(12) as u32 + 1;
+
+    if year < i32::MIN as i64 || year > i32::MAX as i64 {
+        return None;
+    }
+
+    (1..=date.day()).rev()
+                    .find_map(|day| NaiveDate::from_ymd_opt(year as i32, month, day))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A Sunday
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, 18).unwrap()
+    }
+
+    fn resolve(input: &str, direction: Direction) -> String {
+        input.parse::<DateSpec>().unwrap().resolve(today(), direction).unwrap().to_string()
+    }
+
+    fn months_after(date: &str, months: i64) -> Option<String> {
+        add_months(date.parse().unwrap(), months).map(|date| date.to_string())
+    }
+
+    #[test]
+    fn bare_weekday_follows_the_direction() {
+        assert_eq!(resolve("friday", Direction::Future), "2026-10-23");
+        assert_eq!(resolve("friday", Direction::Past), "2026-10-16");
+    }
+
+    #[test]
+    fn weekday_is_never_today() {
+        assert_eq!(resolve("sunday", Direction::Future), "2026-10-25");
+        assert_eq!(resolve("sun", Direction::Past), "2026-10-11");
+    }
+
+    #[test]
+    fn next_and_last_override_the_direction() {
+        assert_eq!(resolve("next monday", Direction::Past), "2026-10-19");
+        assert_eq!(resolve("last Monday", Direction::Future), "2026-10-12");
+    }
+
+    #[test]
+    fn offsets_with_units() {
+        assert_eq!(resolve("3", Direction::Future), "2026-10-21");
+        assert_eq!(resolve("3", Direction::Past), "2026-10-15");
+        assert_eq!(resolve("+2w", Direction::Past), "2026-11-01");
+        assert_eq!(resolve("-1m", Direction::Future), "2026-09-18");
+        assert_eq!(resolve("1y", Direction::Future), "2027-10-18");
+    }
+
+    #[test]
+    fn named_days() {
+        assert_eq!(resolve("today", Direction::Past), "2026-10-18");
+        assert_eq!(resolve("tomorrow", Direction::Past), "2026-10-19");
+        assert_eq!(resolve("yesterday", Direction::Future), "2026-10-17");
+        assert_eq!(resolve("2026-12-24", Direction::Past), "2026-12-24");
+    }
+
+    #[test]
+    fn months_keep_the_day_or_use_the_last_one() {
+        assert_eq!(months_after("2026-01-31", 1).as_deref(), Some("2026-02-28"));
+        assert_eq!(months_after("2024-01-31", 1).as_deref(), Some("2024-02-29"));
+        assert_eq!(months_after("2026-03-31", -1).as_deref(), Some("2026-02-28"));
+        assert_eq!(months_after("2026-11-15", 2).as_deref(), Some("2027-01-15"));
+        assert_eq!(months_after("2024-02-29", -12).as_deref(), Some("2023-02-28"));
+    }
+
+    #[test]
+    fn invalid_dates_are_refused() {
+        assert!("someday".parse::<DateSpec>().is_err());
+        assert!("5x".parse::<DateSpec>().is_err());
+        assert!("next".parse::<DateSpec>().is_err());
+        assert!("next week day".parse::<DateSpec>().is_err());
+        assert!(format!("{}", MAX_OFFSET + 1).parse::<DateSpec>().is_err());
+    }
+
+    #[test]
+    fn out_of_range_offsets_are_errors() {
+        let far = DateSpec::Offset(300_000, Unit::Year, Some(Direction::Future));
+        assert!(far.resolve(today(), Direction::Future).is_err());
+    }
+}
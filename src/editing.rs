@@ -1,12 +1,82 @@
 use std::process::Command;
 use std::io;
 use std::io::prelude::*;
+use std::io::IsTerminal;
 use std::env::temp_dir;
 use std::env;
 use std::fs;
 use std::fs::File;
 use std::path::PathBuf;
 
+#[derive(Debug)]
+enum Source {
+    Editor,
+    Text(String),
+    Stdin,
+}
+
+// Where the text of a message comes from and if it is added after an existing one
+#[derive(Debug)]
+pub struct MessageInput {
+    source: Source,
+    append: bool,
+}
+
+impl MessageInput {
+
+    // Text from the command line first, then piped stdin, then the editor
+    pub fn new(text: Option<String>, append: bool) -> Self {
+
+        let source = match text {
+            Some(text) => Source::Text(text),
+            None => {
+                if io::stdin().is_terminal() {
+                    Source::Editor
+                } else {
+                    Source::Stdin
+                }
+            }
+        };
+
+        MessageInput {
+            source: source,
+            append: append,
+        }
+    }
+
+    pub fn append(&self) -> bool {
+        self.append
+    }
+
+    // The editor always opens on the existing text, otherwise it is replaced or appended to
+    pub fn read(&self, existing: Option<&String>) -> Result<String, io::Error> {
+
+        let text = match &self.source {
+            Source::Editor => {
+                return match existing {
+                    Some(existing) => edit_existing_message(existing),
+                    None => edit_message(),
+                };
+            },
+            Source::Text(text) => {
+                String::from(text.trim_end())
+            },
+            Source::Stdin => {
+                let mut text = String::new();
+                io::stdin().read_to_string(&mut text)?;
+                String::from(text.trim_end())
+            }
+        };
+
+        match existing {
+            Some(existing) if self.append && !text.is_empty() => {
+                Ok(format!("{}\n{}", existing, text))
+            },
+            _ => Ok(text)
+        }
+    }
+}
+
 fn get_env_editor() -> Result<String, env::VarError> {
     let editor = env::var("EDITOR")?;
     Ok(editor)
@@ -15,22 +85,19 @@ fn get_env_editor() -> Result<String, env::VarError> {
 fn choose_editor() -> String {
 
     let env_editor = get_env_editor();
-    let mut editor: String;
 
     match env_editor {
         Ok(env_editor) => {
-            editor = env_editor;
+            env_editor
         },
         Err(_) => {
             println!("Set environnement variable $EDITOR to choose default editor");
             println!("You can use the export command like so : export EDITOR=vim");
 
             // Should test to see what editors are installed on the system
-            editor = String::from("nano");
+            String::from("nano")
         }
     }
-
-    return editor;
 }
 
 pub fn edit_message() -> Result<String, io::Error> {
@@ -79,7 +146,7 @@ pub fn edit_existing_message(existing_message: &String) -> Result<String, io::Er
 pub fn edit_file(file_path: &PathBuf) {
     let editor = choose_editor();
 
-    match Command::new(editor).arg(&file_path).status() {
+    match Command::new(editor).arg(file_path).status() {
         Ok(_) => {},
         Err(error_code) => {
             println!("Opening editor failed with code : {}", error_code);
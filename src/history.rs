@@ -2,55 +2,137 @@ extern crate chrono;
 extern crate serde;
 extern crate serde_json;
 
+use std::collections::BTreeMap;
 use std::fs;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::error::Error;
-use chrono::{NaiveDate, Utc, Duration};
+use chrono::{NaiveDate, NaiveDateTime, Duration};
+use serde_json::Value;
 use serde::{Deserialize, Serialize};
-use super::editing;
-use super::configuration::Configuration;
+use super::editing::MessageInput;
+use super::configuration::{Configuration, Retention};
+use super::archive;
+use super::storage;
+use super::recurrence::Recurrence;
+use super::search::Search;
+use super::clock::Clock;
+use super::template;
+use super::date_format;
 
-// This is used to implement Serialize and Deserialise on the NaiveDate type
-mod json_date_format {
-    use chrono::NaiveDate;
-    use serde::{self, Deserialize, Serializer, Deserializer};
+// How far recurring messages are listed by --upcoming without a number of days
+const UPCOMING_RECURRING_DAYS: i64 = 30;
+// Farther than that --upcoming and upcoming_days are refused
+pub const MAX_UPCOMING_DAYS: i64 = 3660;
 
-    const FORMAT: &'static str = "%Y-%m-%d";
+// Recurrence rules are stored in their RRULE text form
+mod json_rule_format {
+    use super::Recurrence;
+    use serde::{self, Deserialize, Serializer, Deserializer};
 
-    pub fn serialize<S>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer, {
-        let s = format!("{}", date.format(FORMAT));
-        serializer.serialize_str(&s)
+    pub fn serialize<S>(rule: &Recurrence, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer, {
+        serializer.serialize_str(&rule.to_string())
     }
 
-    pub fn deserialize<'de, D>(deserializer: D,) -> Result<NaiveDate, D::Error> where D: Deserializer<'de>, {
+    pub fn deserialize<'de, D>(deserializer: D,) -> Result<Recurrence, D::Error> where D: Deserializer<'de>, {
         let s = String::deserialize(deserializer)?;
-        NaiveDate::parse_from_str(&s, FORMAT).map_err(serde::de::Error::custom)
+        s.parse().map_err(serde::de::Error::custom)
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Message {
-    #[serde(with = "json_date_format")]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Message {
+    // Messages written before IDs existed are read with 0 and given one on load
+    #[serde(default)]
+    id: u64,
+    #[serde(with = "date_format::date")]
     date: NaiveDate,
     text: String,
+    // Already shown by a morning run
+    #[serde(default)]
+    seen: bool,
+    // Restored from the archive, so kept until deleted
+    #[serde(default, skip_serializing_if = "is_false")]
+    restored: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+impl Message {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn date(&self) -> NaiveDate {
+        self.date
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+// A message shown on every date matching its rule, it shares IDs with Message
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RecurringMessage {
+    id: u64,
+    #[serde(with = "json_rule_format")]
+    rule: Recurrence,
+    text: String,
+    // Occurrences up to this date were shown by a morning run
+    #[serde(default, with = "date_format::optional_date")]
+    seen_until: Option<NaiveDate>,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct History {
+    #[serde(default)]
+    next_id: u64,
+    #[serde(rename = "messages")]
     list: Vec<Message>,
+    #[serde(default)]
+    recurring: Vec<RecurringMessage>,
+    #[serde(default, with = "date_format::optional_date")]
+    last_run: Option<NaiveDate>,
+    // Every date of the history is relative to it
+    #[serde(skip)]
+    clock: Clock,
+}
+
+// Older history files were a plain list of messages
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum HistoryFile {
+    Current(History),
+    Legacy(Vec<Message>),
 }
 
 impl History {
 
-    pub fn new() -> Self {
+    pub fn new(clock: Clock) -> Self {
         History{
+            next_id: 1,
             list: Vec::new(),
+            recurring: Vec::new(),
+            last_run: None,
+            clock: clock,
         }
     }
 
-    pub fn load_history(&mut self, history_path: &Path) -> Result<(), Box<dyn Error>> {
+    pub fn today(&self) -> NaiveDate {
+        self.clock.today()
+    }
+
+    // A damaged file is moved aside and what can be read from it is kept
+    // The returned list describes what went wrong
+    pub fn load_history(&mut self, history_path: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut problems: Vec<String> = Vec::new();
+
         self.list.clear();
-        
+        self.recurring.clear();
+
 /*         let json_string = r#"[
                 {
                     "date" : "2019-04-04",
@@ -68,63 +150,373 @@ impl History {
 
         // Check if directory exists
         let parent = history_path.parent().ok_or("Cannot find parent of path constructed with home")?;
-        if !fs::metadata(parent).is_ok() {
+        if fs::metadata(parent).is_err() {
             fs::create_dir(parent)?;
         }
 
+        // Files written before next_id existed do not have one
+        let mut next_id_known = false;
+
         // Check if file exists
         if fs::metadata(history_path).is_ok() {
             let json_string = fs::read_to_string(history_path)?;
-            self.list = serde_json::from_str(&json_string)?;
+
+            match serde_json::from_str(&json_string) {
+                Ok(HistoryFile::Current(history)) => {
+                    let clock = self.clock;
+                    *self = history;
+                    self.clock = clock;
+                    next_id_known = self.next_id > 0;
+                },
+                Ok(HistoryFile::Legacy(list)) => {
+                    self.list = list;
+                },
+                Err(_) => {
+                    problems = self.salvage(&json_string);
+
+                    let broken_path = broken_history_path(history_path, self.clock.now());
+                    fs::rename(history_path, &broken_path)?;
+
+                    problems.push(format!("Recovered {} messages and {} recurring messages",
+                                          self.list.len(), self.recurring.len()));
+                    problems.push(format!("The damaged file was moved to {}", broken_path.display()));
+                }
+            }
         }
 
-        Ok(())
+        // The archived messages keep their ID, it must not be handed out again
+        // The next ID of a damaged file may also be lower than IDs lost with it
+        if !next_id_known || !problems.is_empty() {
+            let max_archived_id = archive::load_archives(history_path)?.iter().map(|m| m.id()).max().unwrap_or(0);
+            if self.next_id <= max_archived_id {
+                self.next_id = max_archived_id + 1;
+            }
+        }
+
+        self.assign_missing_ids();
+
+        Ok(problems)
+    }
+
+    // Keep every entry that can still be read from a damaged history
+    fn salvage(&mut self, json_string: &str) -> Vec<String> {
+
+        let mut problems: Vec<String> = Vec::new();
+
+        match serde_json::from_str::<Value>(json_string) {
+            Ok(Value::Array(entries)) => {
+                self.salvage_messages(entries, &mut problems);
+            },
+            Ok(Value::Object(mut fields)) => {
+                self.next_id = fields.get("next_id").and_then(|v| v.as_u64()).unwrap_or(0);
+
+                if let Some(Value::String(last_run)) = fields.get("last_run") {
+                    self.last_run = NaiveDate::parse_from_str(last_run, "%Y-%m-%d").ok();
+                }
+
+                match fields.remove("messages") {
+                    Some(Value::Array(entries)) => self.salvage_messages(entries, &mut problems),
+                    _ => problems.push(String::from("The list of messages is missing")),
+                }
+
+                if let Some(Value::Array(entries)) = fields.remove("recurring") {
+                    for (i, entry) in entries.into_iter().enumerate() {
+                        match serde_json::from_value::<RecurringMessage>(entry) {
+                            Ok(recurring) => self.recurring.push(recurring),
+                            Err(e) => problems.push(format!("Recurring message {} is invalid : {}", i + 1, e)),
+                        }
+                    }
+                }
+            },
+            Ok(_) => {
+                problems.push(String::from("The history is not a list of messages"));
+            },
+            Err(e) => {
+                // Not valid JSON, pick every complete message object left in the text
+                problems.push(format!("The history is not valid JSON : {}", e));
+
+                self.next_id = salvage_next_id(json_string).unwrap_or(0);
+
+                let mut index = 0;
+
+                while let Some(offset) = json_string[index..].find('{') {
+                    let start = index + offset;
+                    let mut stream = serde_json::Deserializer::from_str(&json_string[start..]).into_iter::<Value>();
+
+                    match stream.next() {
+                        Some(Ok(value)) if value.get("text").is_some() => {
+                            if value.get("rule").is_some() {
+                                if let Ok(recurring) = serde_json::from_value::<RecurringMessage>(value) {
+                                    self.recurring.push(recurring);
+                                }
+                            } else if let Ok(message) = serde_json::from_value::<Message>(value) {
+                                self.list.push(message);
+                            }
+                            index = start + stream.byte_offset();
+                        },
+                        _ => {
+                            index = start + 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        return problems;
+    }
+
+    fn salvage_messages(&mut self, entries: Vec<Value>, problems: &mut Vec<String>) {
+        for (i, entry) in entries.into_iter().enumerate() {
+            match serde_json::from_value::<Message>(entry) {
+                Ok(message) => self.list.push(message),
+                Err(e) => problems.push(format!("Message {} is invalid : {}", i + 1, e)),
+            }
+        }
+    }
+
+    // Check the history and fix what would confuse morning, returns what was changed
+    pub fn repair(&mut self) -> Vec<String> {
+
+        let mut fixes: Vec<String> = Vec::new();
+        let mut used_ids: Vec<u64> = Vec::new();
+
+        for i in 0..self.list.len() {
+            if used_ids.contains(&self.list[i].id) {
+                let id = self.take_id();
+                fixes.push(format!("Message {} of {} had a duplicate ID, it is now {}", self.list[i].id, self.list[i].date, id));
+                self.list[i].id = id;
+            }
+            used_ids.push(self.list[i].id);
+        }
+
+        for i in 0..self.recurring.len() {
+            if used_ids.contains(&self.recurring[i].id) {
+                let id = self.take_id();
+                fixes.push(format!("Recurring message {} had a duplicate ID, it is now {}", self.recurring[i].id, id));
+                self.recurring[i].id = id;
+            }
+            used_ids.push(self.recurring[i].id);
+        }
+
+        let before = self.list.len();
+        self.list.retain(|m| !m.text.trim().is_empty());
+        if self.list.len() != before {
+            fixes.push(format!("Removed {} empty messages", before - self.list.len()));
+        }
+
+        self.list.sort_by_key(|m| (m.date, m.id));
+
+        return fixes;
     }
 
     pub fn write_history(&mut self, conf: &Configuration) -> Result<(), io::Error> {
-        
-        // Remove of bounds messages
-        let date: NaiveDate = (Utc::today() - Duration::days(conf.history_length())).naive_utc();
-        self.list.retain(|s| s.date >= date);
 
-        let json_config = serde_json::to_string(&self.list)?;
-        fs::write(conf.history_path(), json_config)?;
+        let pruned = self.prune(conf.history_length(), conf.retention());
+
+        if conf.retention() == Retention::Archive {
+            archive::archive_messages(conf.history_path(), pruned, conf.compress_archive())?;
+        }
+
+        let json_config = serde_json::to_string(&self)?;
+        storage::write_with_backup(conf.history_path(), json_config.as_bytes())?;
 
         Ok(())
     }
 
-    pub fn add_message(&mut self, date: NaiveDate) -> Result<(), io::Error> {
+    // Takes out the messages older than history_length, unless they are kept
+    // Recurring messages that ended come out as a message on their last day
+    fn prune(&mut self, history_length: i64, retention: Retention) -> Vec<Message> {
 
-        let text = match self.pop_message_by_date(date) {
-            Some(message) => {
-                editing::edit_existing_message(&message)?
-            },
-            None => {
-                editing::edit_message()?
+        // A date given with --date must not prune what is still ahead of the real one
+        if retention == Retention::Keep || self.clock.is_fixed() {
+            return Vec::new();
+        }
+
+        // Remove of bounds messages
+        let date: NaiveDate = self.today() - Duration::days(history_length);
+
+        let (kept, mut pruned): (Vec<Message>, Vec<Message>) = self.list.drain(..)
+                                                                   .partition(|s| s.date >= date || s.restored);
+        self.list = kept;
+
+        let (kept, over): (Vec<RecurringMessage>, Vec<RecurringMessage>) = self.recurring.drain(..)
+                                                                              .partition(|r| r.rule.until().is_none_or(|until| until >= date));
+        self.recurring = kept;
+
+        pruned.extend(over.into_iter().filter_map(|r| {
+            r.rule.until().map(|until| Message{
+                id:       r.id,
+                date:     until,
+                text:     r.text,
+                seen:     true,
+                restored: false,
+            })
+        }));
+
+        return pruned;
+    }
+
+    fn assign_missing_ids(&mut self) {
+
+        // Never hand out an ID that is already used, even if the file was edited by hand
+        let max_id = self.list.iter().map(|m| m.id)
+                              .chain(self.recurring.iter().map(|r| r.id))
+                              .max().unwrap_or(0);
+        if self.next_id <= max_id {
+            self.next_id = max_id + 1;
+        }
+
+        for i in 0..self.list.len() {
+            if self.list[i].id == 0 {
+                self.list[i].id = self.take_id();
             }
-        };
+        }
+    }
+
+    fn take_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        return id;
+    }
+
+    // With append the text goes after the last message of that date when there is one
+    // Returns the ID of the message and whether it was appended to
+    pub fn add_message(&mut self, date: NaiveDate, input: &MessageInput) -> Result<(u64, bool), io::Error> {
+
+        if input.append() {
+            if let Some(message) = self.list.iter_mut().filter(|m| m.date == date).max_by_key(|m| m.id) {
+                let text = input.read(Some(&message.text))?;
+
+                if text.is_empty() {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, "Message is empty"));
+                }
+
+                message.text = text;
+                message.seen = false;
+                return Ok((message.id, true));
+            }
+        }
+
+        let text = input.read(None)?;
+
+        if text.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Message is empty"));
+        }
+
+        let id = self.take_id();
 
         self.list.push(
             Message{
-                date: date,
-                text: text,
+                id:       id,
+                date:     date,
+                text:     text,
+                seen:     false,
+                restored: false,
             }
         );
 
-        Ok(())
+        Ok((id, false))
     }
 
-    pub fn add_delayed_message(&mut self, nb_days: i64) -> Result<(), io::Error> {
-        let date: NaiveDate = (Utc::today() + Duration::days(nb_days)).naive_utc();
+    pub fn edit_message(&mut self, id: u64, input: &MessageInput) -> Result<(), io::Error> {
+
+        let message = self.find_message_by_id(id)?;
+        let text = input.read(Some(&message.text))?;
+
+        // Use delete to remove a message
+        if text.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Message is empty"));
+        }
+
+        message.text = text;
 
-        self.add_message(date)?;
         Ok(())
     }
-    
+
+    pub fn delete_message(&mut self, id: u64) -> Result<Message, io::Error> {
+
+        match self.list.iter().position(|m| m.id == id) {
+            Some(index) => {
+                Ok(self.list.remove(index))
+            },
+            None => {
+                Err(no_message_error(id))
+            }
+        }
+    }
+
+    // The history is written before the message leaves the archive, so it is always in one of them
+    pub fn restore_message(&mut self, id: u64, conf: &Configuration) -> Result<NaiveDate, io::Error> {
+
+        if self.list.iter().any(|m| m.id == id) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("Message {} is already in the history", id)));
+        }
+
+        let mut message = archive::find_message(conf.history_path(), id)?;
+        message.restored = true;
+
+        let date = message.date;
+        self.list.push(message);
+
+        if let Err(e) = self.write_history(conf) {
+            self.list.retain(|m| m.id != id);
+            return Err(e);
+        }
+
+        archive::remove_message(conf.history_path(), id, conf.compress_archive())?;
+
+        Ok(date)
+    }
+
+    pub fn add_recurring_message(&mut self, rule: Recurrence, input: &MessageInput) -> Result<u64, io::Error> {
+
+        let text = input.read(None)?;
+
+        if text.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Message is empty"));
+        }
+
+        let id = self.take_id();
+
+        self.recurring.push(
+            RecurringMessage{
+                id:         id,
+                rule:       rule,
+                text:       text,
+                seen_until: None,
+            }
+        );
+
+        Ok(id)
+    }
+
+    pub fn cancel_recurring_message(&mut self, id: u64) -> Result<RecurringMessage, io::Error> {
+
+        match self.recurring.iter().position(|r| r.id == id) {
+            Some(index) => {
+                Ok(self.recurring.remove(index))
+            },
+            None => {
+                Err(io::Error::new(io::ErrorKind::NotFound, format!("No recurring message with ID {}", id)))
+            }
+        }
+    }
+
+    fn find_message_by_id(&mut self, id: u64) -> Result<&mut Message, io::Error> {
+        self.list.iter_mut()
+                 .find(|m| m.id == id)
+                 .ok_or_else(|| no_message_error(id))
+    }
+
+    // The routine already ran on this day
+    pub fn ran_today(&self) -> bool {
+        self.last_run.is_some_and(|last_run| last_run >= self.today())
+    }
+
     #[allow(dead_code)]
     pub fn print_history(&self) {
         println!("----------History---------");
-        for message in &self.list 
+        for message in &self.list
         {
             println!("Date : {}", message.date);
             println!("{}", message.text);
@@ -132,67 +524,506 @@ impl History {
         println!("--------------------------");
     }
 
-    pub fn find_message_by_nb_day(&self, nb_days: i64) -> Option<&String> {
-        let lookup_date: NaiveDate = (Utc::today() + Duration::days(nb_days)).naive_utc();
+    pub fn print_message_list(&self) {
 
-        for message in &self.list 
+        let mut messages: Vec<&Message> = self.list.iter().collect();
+        messages.sort_by_key(|m| (m.date, m.id));
+
+        if messages.is_empty() {
+            println!("No messages");
+        }
+
+        for message in messages
         {
-            if message.date == lookup_date 
-            {
-                return Some(&message.text)
-            }
+            println!("[{}] {}", message.id, message.date);
+            println!("{}", message.text);
+            println!();
         }
-        return None;
     }
 
-    fn find_message_by_date(&self, date: NaiveDate) -> Option<&String> {
+    // Archived messages are given separately as they are not loaded with the history
+    pub fn print_search_results(&self, search: &Search, archived: &[Message]) -> Result<(), Box<dyn Error>> {
+
+        let (from, to) = search.date_range(self.today())?;
+
+        let mut messages: Vec<&Message> = self.list.iter()
+            .chain(archived.iter())
+            .filter(|m| from.is_none_or(|from| m.date >= from))
+            .filter(|m| to.is_none_or(|to| m.date <= to))
+            .filter(|m| search.is_match(&m.text))
+            .collect();
+        messages.sort_by_key(|m| (m.date, m.id));
 
-        for message in &self.list 
+        let recurring: Vec<&RecurringMessage> = self.recurring.iter()
+            .filter(|r| search.is_match(&r.text))
+            .filter(|r| r.rule.occurs_between(from, to))
+            .collect();
+
+        if messages.is_empty() && recurring.is_empty() {
+            println!("No message found");
+        }
+
+        for message in messages
         {
-            if message.date == date 
-            {
-                return Some(&message.text);
+            if archived.iter().any(|a| std::ptr::eq(a, message)) {
+                println!("[{}] {} (archived)", message.id, message.date);
+            } else {
+                println!("[{}] {}", message.id, message.date);
             }
+            println!("{}", search.highlight(&message.text));
+            println!();
         }
 
-        return None;
+        for recurring in recurring
+        {
+            println!("[{}] {}", recurring.id, recurring.rule);
+            println!("{}", search.highlight(&recurring.text));
+            println!();
+        }
+
+        Ok(())
     }
 
-    fn pop_message_by_date(&mut self, date: NaiveDate) -> Option<String> {
+    pub fn print_recurring_list(&self) {
 
-        let mut pop_message: Option<String> = None;
+        let today = self.today();
 
-        for message in &self.list 
+        if self.recurring.is_empty() {
+            println!("No recurring messages");
+        }
+
+        for recurring in &self.recurring
         {
-            if message.date == date 
-            {
-                pop_message = Some(message.text.clone());
+            match recurring.rule.next_occurrence(today) {
+                Some(next) => println!("[{}] {} (next on {})", recurring.id, recurring.rule, next),
+                None => println!("[{}] {} (no upcoming occurrence)", recurring.id, recurring.rule),
             }
+            println!("{}", recurring.text);
+            println!();
         }
+    }
 
-        match pop_message {
-            Some(pop_message) => {
-                self.list.retain(|s| s.date != date);
-                return Some(pop_message);
-            },
-            None => {
-                return None;
+    // Dated messages first, then the recurring ones falling on that date
+    pub fn find_messages_by_date(&self, date: NaiveDate) -> Vec<&String> {
+
+        let mut messages: Vec<&Message> = self.list.iter()
+                                                   .filter(|m| m.date == date)
+                                                   .collect();
+        messages.sort_by_key(|m| m.id);
+
+        let mut texts: Vec<&String> = messages.iter().map(|m| &m.text).collect();
+
+        for recurring in &self.recurring {
+            if recurring.rule.occurs_on(date) {
+                texts.push(&recurring.text);
+            }
+        }
+
+        return texts;
+    }
+
+    // Messages of the day as the morning would show them, nothing is marked seen
+    pub fn print_preview_message(&self) {
+
+        let date = self.today();
+
+        println!("------------------- Message ---------------------");
+        println!("{} {}", date, date.format("%A"));
+
+        let messages = self.find_messages_by_date(date);
+
+        if messages.is_empty() {
+            println!("No message for this day");
+        }
+        print_messages(&messages, date);
+    }
+
+    // Messages after today up to last_day and recurring messages up to last_recurring_day
+    // Grouped by date with their ID
+    fn upcoming(&self, last_day: NaiveDate, last_recurring_day: NaiveDate) -> Vec<(NaiveDate, Vec<(u64, &String)>)> {
+
+        let today = self.today();
+        let mut days: BTreeMap<NaiveDate, Vec<(u64, &String)>> = BTreeMap::new();
+
+        let mut messages: Vec<&Message> = self.list.iter()
+                                                   .filter(|m| m.date > today && m.date <= last_day)
+                                                   .collect();
+        messages.sort_by_key(|m| (m.date, m.id));
+
+        for message in messages {
+            days.entry(message.date).or_default().push((message.id, &message.text));
+        }
+
+        for recurring in &self.recurring {
+            let mut from = today.succ_opt();
+
+            while let Some(date) = from.and_then(|from| recurring.rule.next_occurrence(from)) {
+                if date > last_recurring_day {
+                    break;
+                }
+                days.entry(date).or_default().push((recurring.id, &recurring.text));
+                from = date.succ_opt();
             }
         }
+
+        return days.into_iter().collect();
+    }
+
+    // Last day shown when looking that many days ahead
+    fn upcoming_last_day(&self, days: i64) -> Result<NaiveDate, io::Error> {
+
+        if days > MAX_UPCOMING_DAYS {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Cannot look more than {} days ahead", MAX_UPCOMING_DAYS)));
+        }
+
+        self.today().checked_add_signed(Duration::days(days))
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Cannot look {} days ahead", days)))
     }
 
-    pub fn print_today_message(&self) {
-        let message = self.find_message_by_nb_day(0);
+    // Without a number of days, every future message is listed
+    // Recurring messages go on forever so they are only listed for the default number of days
+    pub fn print_upcoming(&self, days: Option<i64>) -> Result<(), io::Error> {
+
+        let today = self.today();
 
-        match message {
-            Some(message) => {
-                println!("------------------- Message ---------------------");
-                println!("{}", message);
+        let upcoming = match days {
+            Some(days) => {
+                let last_day = self.upcoming_last_day(days)?;
+                self.upcoming(last_day, last_day)
             },
             None => {
-                println!("------------------- Message ---------------------");
-                println!("No message for today");
+                let last_recurring_day = self.upcoming_last_day(UPCOMING_RECURRING_DAYS)?;
+                let last_message = self.list.iter().map(|m| m.date).max().unwrap_or(today);
+                self.upcoming(last_message.max(last_recurring_day), last_recurring_day)
+            }
+        };
+
+        if upcoming.is_empty() {
+            println!("No upcoming messages");
+        }
+
+        for (i, (date, texts)) in upcoming.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            println!("{} {}", date, date.format("%A"));
+            for (id, text) in texts {
+                println!("[{}] {}", id, template::render(text, today));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Section of the morning with the messages of the next days, nothing is marked seen
+    pub fn print_coming_up(&self, days: i64) -> Result<(), io::Error> {
+
+        let last_day = self.upcoming_last_day(days)?;
+        let upcoming = self.upcoming(last_day, last_day);
+
+        // Do not print the section if there is nothing to write
+        if upcoming.is_empty() {
+            return Ok(());
+        }
+
+        println!("------------------- Coming up -------------------");
+
+        for (i, (date, texts)) in upcoming.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            println!("{} {}", date, date.format("%A"));
+            let texts: Vec<&String> = texts.iter().map(|(_, text)| *text).collect();
+            print_messages(&texts, self.today());
+        }
+
+        Ok(())
+    }
+
+    // Shows every message not seen since the last run, grouped by date, and marks them seen
+    // The messages of today are shown on every run, seen or not
+    // A date given with --date only shows them, the real next run still gets them
+    pub fn print_today_message(&mut self, history_length: i64) {
+
+        let today = self.today();
+
+        let mut start = match self.last_run {
+            Some(last_run) if last_run < today => last_run,
+            _ => today,
+        };
+
+        // Older messages are out of the history anyway
+        let oldest = today - Duration::days(history_length);
+        if start < oldest {
+            start = oldest;
+        }
+
+        let mut days: Vec<(NaiveDate, Vec<&String>)> = Vec::new();
+        let mut date = start;
+
+        while date <= today {
+            let mut messages: Vec<&Message> = self.list.iter()
+                                                       .filter(|m| m.date == date && (date == today || !m.seen))
+                                                       .collect();
+            messages.sort_by_key(|m| m.id);
+
+            let mut texts: Vec<&String> = messages.iter().map(|m| &m.text).collect();
+
+            for recurring in &self.recurring {
+                let already_seen = date < today && recurring.seen_until.is_some_and(|seen| seen >= date);
+                if !already_seen && recurring.rule.occurs_on(date) {
+                    texts.push(&recurring.text);
+                }
+            }
+
+            if !texts.is_empty() {
+                days.push((date, texts));
             }
+
+            date += Duration::days(1);
+        }
+
+        println!("------------------- Message ---------------------");
+
+        if days.is_empty() {
+            println!("No message for today");
+        } else if days.len() == 1 && days[0].0 == today {
+            print_messages(&days[0].1, today);
+        } else {
+            for (i, (date, texts)) in days.iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
+                if *date == today {
+                    println!("{} {} (today)", date, date.format("%A"));
+                } else {
+                    println!("{} {}", date, date.format("%A"));
+                }
+                print_messages(texts, today);
+            }
+        }
+
+        if self.clock.is_fixed() {
+            return;
+        }
+
+        for message in self.list.iter_mut().filter(|m| m.date >= start && m.date <= today) {
+            message.seen = true;
+        }
+
+        for recurring in self.recurring.iter_mut() {
+            recurring.seen_until = Some(today);
+        }
+
+        self.last_run = Some(today);
+    }
+}
+
+// Placeholders are filled as of the day they are shown, not the date of the message
+pub fn print_messages(messages: &[&String], today: NaiveDate) {
+
+    for (i, message) in messages.iter().enumerate() {
+        // Keep a blank line between notes of the same day
+        if i > 0 {
+            println!();
         }
+        println!("{}", template::render(message, today));
+    }
+}
+
+// The next ID is the first field of the file, so it is still there when the end is cut off
+fn salvage_next_id(json_string: &str) -> Option<u64> {
+
+    let start = json_string.find("\"next_id\"")? + "\"next_id\"".len();
+    let value = json_string[start..].trim_start().strip_prefix(':')?.trim_start();
+    let end = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+
+    value[..end].parse().ok()
+}
+
+fn broken_history_path(history_path: &Path, now: NaiveDateTime) -> PathBuf {
+    let file_name = history_path.file_name().and_then(|n| n.to_str()).unwrap_or("history.json");
+    history_path.with_file_name(format!("{}.broken-{}", file_name, now.format("%Y%m%d-%H%M%S")))
+}
+
+fn no_message_error(id: u64) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("No message with ID {}", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> History {
+        History::new(Clock::Fixed(NaiveDate::from_ymd_opt(2026, 10, 18).unwrap()))
+    }
+
+    #[test]
+    fn salvage_keeps_the_valid_messages_of_a_list() {
+        let mut history = history();
+        let problems = history.salvage(r#"[
+            {"id": 1, "date": "2026-10-01", "text": "kept"},
+            {"id": 2, "date": "not a date", "text": "lost"},
+            {"id": 3, "date": "2026-10-02", "text": "also kept"}
+        ]"#);
+
+        let texts: Vec<&str> = history.list.iter().map(|m| m.text.as_str()).collect();
+        assert_eq!(texts, ["kept", "also kept"]);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("Message 2 is invalid"));
+    }
+
+    #[test]
+    fn salvage_keeps_the_fields_of_an_object() {
+        let mut history = history();
+        let problems = history.salvage(r#"{
+            "next_id": 9,
+            "last_run": "2026-10-17",
+            "messages": [{"id": 4, "date": "2026-10-01", "text": "kept"}],
+            "recurring": [
+                {"id": 5, "rule": "FREQ=WEEKLY;DTSTART=2026-10-05", "text": "weekly"},
+                {"id": 6, "rule": "FREQ=SOMETIMES", "text": "lost"}
+            ]
+        }"#);
+
+        assert_eq!(history.next_id, 9);
+        assert_eq!(history.last_run, NaiveDate::from_ymd_opt(2026, 10, 17));
+        assert_eq!(history.list.len(), 1);
+        assert_eq!(history.recurring.len(), 1);
+        assert_eq!(history.recurring[0].text, "weekly");
+        assert_eq!(problems.len(), 1);
+    }
+
+    #[test]
+    fn salvage_picks_complete_messages_from_broken_json() {
+        let mut history = history();
+        let problems = history.salvage(r#"{"next_id": 4, "messages": [
+            {"id": 1, "date": "2026-10-01", "text": "first"},
+            {"id": 2, "date": "2026-10-02", "text": "second"},
+            {"id": 3, "date": "2026-10-"#);
+
+        let ids: Vec<u64> = history.list.iter().map(|m| m.id).collect();
+        assert_eq!(ids, [1, 2]);
+        assert_eq!(history.next_id, 4);
+        assert!(problems[0].starts_with("The history is not valid JSON"));
+    }
+
+    #[test]
+    fn salvage_refuses_other_json() {
+        let mut history = history();
+        let problems = history.salvage("42");
+
+        assert!(history.list.is_empty());
+        assert_eq!(problems, ["The history is not a list of messages"]);
+    }
+
+    #[test]
+    fn missing_and_duplicate_ids_are_replaced() {
+        let mut history = history();
+        history.salvage(r#"[
+            {"date": "2026-10-01", "text": "no id"},
+            {"id": 7, "date": "2026-10-02", "text": "seven"},
+            {"id": 7, "date": "2026-10-03", "text": "seven again"},
+            {"id": 8, "date": "2026-10-04", "text": "  "}
+        ]"#);
+        history.assign_missing_ids();
+
+        assert_eq!(history.list[0].id, 9);
+
+        let fixes = history.repair();
+        let ids: Vec<u64> = history.list.iter().map(|m| m.id).collect();
+
+        assert_eq!(ids, [9, 7, 10]);
+        assert_eq!(fixes.len(), 2);
+    }
+
+    // Messages of 2026-10-01 and 2026-10-18, and a daily message that ended on 2026-10-02
+    fn history_on(clock: Clock) -> History {
+        let mut history: History = serde_json::from_str(r#"{
+            "next_id": 4,
+            "last_run": "2026-10-17",
+            "messages": [
+                {"id": 1, "date": "2026-10-01", "text": "old"},
+                {"id": 2, "date": "2026-10-18", "text": "today"}
+            ],
+            "recurring": [{"id": 3, "rule": "FREQ=DAILY;DTSTART=2026-09-01;UNTIL=2026-10-02", "text": "ended"}]
+        }"#).unwrap();
+        history.clock = clock;
+        history
+    }
+
+    #[test]
+    fn fixed_clock_prunes_nothing() {
+        let mut history = history_on(Clock::Fixed(NaiveDate::from_ymd_opt(2026, 11, 17).unwrap()));
+
+        assert!(history.prune(15, Retention::Archive).is_empty());
+        assert!(history.prune(15, Retention::Delete).is_empty());
+        assert_eq!(history.list.len(), 2);
+        assert_eq!(history.recurring.len(), 1);
+    }
+
+    #[test]
+    fn fixed_clock_marks_nothing_seen() {
+        let mut history = history_on(Clock::Fixed(NaiveDate::from_ymd_opt(2026, 10, 18).unwrap()));
+
+        history.print_today_message(15);
+
+        assert!(history.list.iter().all(|m| !m.seen));
+        assert_eq!(history.recurring[0].seen_until, None);
+        assert_eq!(history.last_run, NaiveDate::from_ymd_opt(2026, 10, 17));
+    }
+
+    #[test]
+    fn old_and_ended_messages_are_pruned() {
+        let mut history = history_on(Clock::Fixed(NaiveDate::from_ymd_opt(2026, 10, 18).unwrap()));
+        history.clock = Clock::default();
+
+        let pruned = history.prune(15, Retention::Archive);
+        let pruned: Vec<(u64, String)> = pruned.iter().map(|m| (m.id, m.date.to_string())).collect();
+
+        assert_eq!(pruned, [(1, String::from("2026-10-01")), (3, String::from("2026-10-02"))]);
+        assert_eq!(history.list.len(), 1);
+        assert!(history.recurring.is_empty());
+    }
+
+    #[test]
+    fn damaged_file_is_moved_aside() {
+        let directory = std::env::temp_dir().join(format!("morning-history-test-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("history.json");
+        fs::write(&path, r#"[{"id": 1, "date": "2026-10-01", "text": "kept"}, {"id": "#).unwrap();
+
+        let mut history = history();
+        let problems = history.load_history(&path).unwrap();
+
+        let broken: Vec<PathBuf> = fs::read_dir(&directory).unwrap()
+                                                           .map(|entry| entry.unwrap().path())
+                                                           .collect();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(history.list.len(), 1);
+        assert!(problems.iter().any(|p| p.starts_with("Recovered 1 messages")));
+        assert_eq!(broken.len(), 1);
+        assert!(broken[0].to_string_lossy().contains("history.json.broken-"));
+    }
+
+    #[test]
+    fn archived_ids_are_not_handed_out_again() {
+        let directory = std::env::temp_dir().join(format!("morning-archive-test-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("history.json");
+        fs::write(&path, r#"[{"id": 2, "date": "2026-10-01", "text": "kept"}]"#).unwrap();
+        fs::write(directory.join("history.2026-09.json"), r#"[{"id": 7, "date": "2026-09-01", "text": "old"}]"#).unwrap();
+
+        let mut history = history();
+        history.load_history(&path).unwrap();
+
+        let reused: Message = serde_json::from_str(r#"{"id": 7, "date": "2026-09-02", "text": "other"}"#).unwrap();
+        archive::archive_messages(&path, vec![reused.clone(), reused], false).unwrap();
+        let archived = archive::load_archives(&path).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(history.next_id, 8);
+        assert_eq!(archived.len(), 2);
     }
 }
@@ -4,15 +4,47 @@ mod history;
 mod editing;
 mod reminder;
 mod command;
+mod recurrence;
+mod dates;
+mod search;
+mod archive;
+mod clock;
+mod storage;
+mod pty;
+mod command_list;
+mod shell;
+mod condition;
+mod template;
+mod date_format;
 
 use arguments::ArgParser;
+use dates::Direction;
+use recurrence::Recurrence;
+use clock::Clock;
 use configuration::Configuration;
 use history::History;
 
 fn main() {
 
+    let argparser = ArgParser::new();
+
+    // Parse command line arguments
+    let parsed = argparser.parser();
+
+    // Printed alone so it can be given to eval
+    if let Ok(arguments::Action::ShellInit(shell, hook)) = parsed {
+        print!("{}", shell::init_script(shell));
+        if hook {
+            print!("{}", shell::hook_script(shell));
+        }
+        return;
+    }
+
+    // Nothing is shown when the routine already ran today
+    let once = matches!(parsed, Ok(arguments::Action::Once(false)));
+
     // Read configuration
-    let mut conf =  match Configuration::new() {
+    let conf =  match Configuration::new() {
         Ok(conf)  => {
             conf
         },
@@ -24,83 +56,344 @@ fn main() {
     };
 
     // Print portable header if it is
-    if conf.is_portable(){
+    if conf.is_portable() && !once {
         println!("----- Morning is in portable mode -----");
         println!();
     }
 
+    // The real date, unless another one is given
+    let system_clock = Clock::system(&conf);
+
+    let clock = match argparser.date() {
+        Ok(Some(date)) => {
+            match date.resolve(system_clock.today(), Direction::Future) {
+                Ok(date) => Clock::Fixed(date),
+                Err(e) => {
+                    println!("{}", e);
+                    return;
+                }
+            }
+        },
+        Ok(None) => system_clock,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+
+    let today = clock.today();
+
+    if clock.is_fixed() && !once {
+        println!("Using {} {} as today", today, today.format("%A"));
+        println!();
+    }
+
+    // Lock the data directory until everything is written
+    let data_dir = match conf.history_path().parent() {
+        Some(data_dir) => data_dir.to_path_buf(),
+        None => {
+            println!("Cannot find the directory of the history file");
+            return;
+        }
+    };
+
+    let lock = if once {
+        storage::try_lock_data_dir(&data_dir)
+    } else {
+        storage::lock_data_dir(&data_dir).map(Some)
+    };
+
+    let _lock = match lock {
+        Ok(Some(lock)) => lock,
+        Ok(None) => {
+            // Another morning is running the routine right now
+            return;
+        },
+        Err(e) => {
+            println!("Error while locking {}", data_dir.display());
+            println!("{}", e);
+            return;
+        }
+    };
+
     // Load history
-    let mut history = History::new();
+    let mut history = History::new(clock);
 
-    match history.load_history(conf.history_path().as_path()) {
-        Ok(_) => {},
+    // Keep going without messages if the history cannot be read, but never overwrite it
+    let history_loaded = match history.load_history(conf.history_path().as_path()) {
+        Ok(problems) => {
+            if !problems.is_empty() {
+                println!("The history file was damaged");
+                for problem in problems {
+                    println!("{}", problem);
+                }
+                println!();
+            }
+            true
+        },
         Err(e) => {
             println!("Error while loading history");
             println!("{}", e);
-            return;
+            println!();
+            false
         }
     };
 
-    let argparser = ArgParser::new();
+    if once {
+        if history.ran_today() {
+            return;
+        }
 
-    // Parse command line arguments
-    match argparser.parser() {
+        if conf.is_portable() {
+            println!("----- Morning is in portable mode -----");
+            println!();
+        }
+    }
+
+    match parsed {
         Ok(action) => {
             match action {
                 arguments::Action::Help => {
                     argparser.print_help();
                     return;
                 },
-                arguments::Action::Message(nb_of_days) => {
-                    println!("Message for {} days", nb_of_days);
+                arguments::Action::Message(date, input) => {
+                    let date = match date.resolve(today, Direction::Future) {
+                        Ok(date) => date,
+                        Err(e) => {
+                            println!("{}", e);
+                            return;
+                        }
+                    };
 
-                    match history.add_delayed_message(nb_of_days) {
-                        Ok(_) => {
-                            println!("Message added");
+                    println!("Message for {}", date);
+
+                    match history.add_message(date, &input) {
+                        Ok((id, true)) => {
+                            println!("Message {} appended", id);
+                        },
+                        Ok((id, false)) => {
+                            println!("Message {} added", id);
                         },
-                        Err(_) => {
+                        Err(e) => {
                             println!("Could not get message");
+                            println!("{}", e);
                         }
                     }
                 },
-                arguments::Action::Past(nb_of_days) => {
-                    println!("Show past message of {} days", nb_of_days);
+                arguments::Action::Past(date) => {
+                    let date = match date.resolve(today, Direction::Past) {
+                        Ok(date) => date,
+                        Err(e) => {
+                            println!("{}", e);
+                            return;
+                        }
+                    };
+
+                    println!("Show past message of {}", date);
 
-                    let message = history.find_message_by_nb_day(nb_of_days * -1);
-                    match message {
-                        Some(message_string) => {
-                            println!("{}", message_string);
+                    let messages = history.find_messages_by_date(date);
+                    if messages.is_empty() {
+                        println!("No message for this day");
+                    }
+                    history::print_messages(&messages, today);
+                },
+                arguments::Action::List => {
+                    history.print_message_list();
+                },
+                arguments::Action::Edit(id, input) => {
+                    match history.edit_message(id, &input) {
+                        Ok(_) => {
+                            println!("Message {} edited", id);
                         },
-                        None => {
-                            println!("No message for this day");
+                        Err(e) => {
+                            println!("Could not edit message");
+                            println!("{}", e);
                         }
                     }
                 },
+                arguments::Action::Delete(id) => {
+                    match history.delete_message(id) {
+                        Ok(message) => {
+                            println!("Message {} for {} deleted", id, message.date());
+                        },
+                        Err(e) => {
+                            println!("Could not delete message");
+                            println!("{}", e);
+                        }
+                    }
+                },
+                arguments::Action::Recur(rule, input) => {
+                    let rule = match Recurrence::parse(&rule, Some(today)) {
+                        Ok(rule) => rule,
+                        Err(e) => {
+                            println!("{}", e);
+                            return;
+                        }
+                    };
+
+                    println!("Recurring message for {}", rule);
+
+                    match history.add_recurring_message(rule, &input) {
+                        Ok(id) => {
+                            println!("Recurring message {} added", id);
+                        },
+                        Err(e) => {
+                            println!("Could not get message");
+                            println!("{}", e);
+                        }
+                    }
+                },
+                arguments::Action::Recurring => {
+                    history.print_recurring_list();
+                },
+                arguments::Action::Cancel(id) => {
+                    match history.cancel_recurring_message(id) {
+                        Ok(_) => {
+                            println!("Recurring message {} cancelled", id);
+                        },
+                        Err(e) => {
+                            println!("Could not cancel recurring message");
+                            println!("{}", e);
+                        }
+                    }
+                },
+                arguments::Action::Search(search) => {
+                    let archived = if search.include_archives() {
+                        match archive::load_archives(conf.history_path()) {
+                            Ok(archived) => archived,
+                            Err(e) => {
+                                println!("Could not read the archive");
+                                println!("{}", e);
+                                Vec::new()
+                            }
+                        }
+                    } else {
+                        Vec::new()
+                    };
+
+                    match history.print_search_results(&search, &archived) {
+                        Ok(_) => {},
+                        Err(e) => {
+                            println!("{}", e);
+                        }
+                    }
+                },
+                arguments::Action::Restore(id) => {
+                    if history_loaded {
+                        match history.restore_message(id, &conf) {
+                            Ok(date) => {
+                                println!("Message {} for {} restored", id, date);
+                            },
+                            Err(e) => {
+                                println!("Could not restore message");
+                                println!("{}", e);
+                            }
+                        }
+                    } else {
+                        println!("The history could not be loaded, nothing was restored");
+                    }
+                },
+                arguments::Action::RepairHistory => {
+                    if history_loaded {
+                        let fixes = history.repair();
+                        for fix in &fixes {
+                            println!("{}", fix);
+                        }
+                        println!("History checked, {} problems fixed", fixes.len());
+                    } else {
+                        println!("The history could not be loaded, nothing was repaired");
+                    }
+                },
                 arguments::Action::Command => {
                     command::edit_command(&conf);
                 },
                 arguments::Action::Reminder => {
                     reminder::edit_reminder(&conf);
                 },
-                arguments::Action::Morning => {
-                    command::execute_commands(&conf);
-                    reminder::print_reminder(&conf);
-                    history.print_today_message();
+                arguments::Action::Upcoming(days) => {
+                    if let Err(e) = history.print_upcoming(days) {
+                        println!("Could not list upcoming messages");
+                        println!("{}", e);
+                    }
+                },
+                arguments::Action::ReminderList => {
+                    if let Err(e) = reminder::print_reminder_list(&conf, today) {
+                        println!("Could not list reminders");
+                        println!("{}", e);
+                    }
+                },
+                arguments::Action::ReminderSnooze(id, date) => {
+                    let date = match date.resolve(today, Direction::Future) {
+                        Ok(date) => date,
+                        Err(e) => {
+                            println!("{}", e);
+                            return;
+                        }
+                    };
+
+                    match reminder::snooze_reminder(&conf, &id, date, &clock) {
+                        Ok(_) => {
+                            println!("Reminder {} snoozed until {}", id, date);
+                        },
+                        Err(e) => {
+                            println!("Could not snooze reminder");
+                            println!("{}", e);
+                        }
+                    }
+                },
+                arguments::Action::ReminderDone(id) => {
+                    match reminder::complete_reminder(&conf, &id, &clock) {
+                        Ok(_) => {
+                            println!("Reminder {} done", id);
+                        },
+                        Err(e) => {
+                            println!("Could not mark reminder as done");
+                            println!("{}", e);
+                        }
+                    }
+                },
+                arguments::Action::ShellInit(_, _) => {},
+                arguments::Action::DryRun => {
+                    println!("Preview of the morning of {}", today);
+                    println!();
+
+                    command::preview_commands(&conf, today);
+                    reminder::print_reminder(&conf, today);
+                    history.print_preview_message();
+
+                    // Nothing changed, leave the files as they are
+                    return;
+                },
+                arguments::Action::Morning | arguments::Action::Once(_) => {
+                    command::execute_commands(&conf, today);
+                    reminder::print_reminder(&conf, today);
+                    history.print_today_message(conf.history_length());
+
+                    if conf.upcoming_days() > 0 {
+                        if let Err(e) = history.print_coming_up(conf.upcoming_days()) {
+                            println!("Could not show the coming up messages");
+                            println!("{}", e);
+                        }
+                    }
                 },
             }
         },
-        Err(_) => {
+        Err(e) => {
+            println!("{}", e);
             argparser.print_help();
             return;
         }
     }
 
     // Write history
-    match history.write_history(&conf) {
-        Ok(_) => {},
-        Err(e) => {
-            println!("Error while writing history");
-            println!("{}", e);
+    if history_loaded {
+        match history.write_history(&conf) {
+            Ok(_) => {},
+            Err(e) => {
+                println!("Error while writing history");
+                println!("{}", e);
+            }
         }
     }
 
@@ -0,0 +1,161 @@
+extern crate libc;
+
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::IsTerminal;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Mutex, Once};
+use std::thread;
+
+const DEFAULT_ROWS: u16 = 24;
+const DEFAULT_COLS: u16 = 80;
+
+// Keys typed are sent to the pseudo-terminal of the command running at that time
+static INPUT_TARGET: Mutex<Option<File>> = Mutex::new(None);
+static INPUT_THREAD: Once = Once::new();
+
+// Terminal settings restored when the command is done
+pub struct RawTerminal {
+    original: libc::termios,
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+
+        if let Ok(mut target) = INPUT_TARGET.lock() {
+            *target = None;
+        }
+    }
+}
+
+// Starts the command with a pseudo-terminal as its terminal, so it keeps colours and progress bars
+// Returns the master side to read the output from, it ends with an EIO error
+// With interactive, it reads its input from the pseudo-terminal
+// When morning runs in a terminal, the keys typed are forwarded to it until the RawTerminal is dropped
+// Prompts reading the terminal itself, like sudo, need them even without interactive
+pub fn spawn(mut command: Command, interactive: bool) -> Result<(Child, File, Option<RawTerminal>), io::Error> {
+
+    let size = terminal_size();
+    let mut master: RawFd = -1;
+    let mut slave: RawFd = -1;
+
+    let result = unsafe {
+        libc::openpty(&mut master, &mut slave, std::ptr::null_mut(), std::ptr::null(), &size)
+    };
+
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let master = unsafe { OwnedFd::from_raw_fd(master) };
+    let slave = unsafe { OwnedFd::from_raw_fd(slave) };
+
+    // The child must not keep the master side open
+    unsafe {
+        libc::fcntl(master.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC);
+    }
+
+    let stdin = if interactive {
+        Stdio::from(slave.try_clone()?)
+    } else {
+        Stdio::null()
+    };
+
+    command.stdin(stdin)
+           .stdout(Stdio::from(slave.try_clone()?))
+           .stderr(Stdio::from(slave.try_clone()?));
+
+    unsafe {
+        command.pre_exec(|| {
+            // New session with the pseudo-terminal as controlling terminal
+            if libc::setsid() == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::ioctl(0, libc::TIOCSCTTY, 0) == -1 && libc::ioctl(1, libc::TIOCSCTTY, 0) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+
+    let child = command.spawn()?;
+
+    // Only the child must hold the slave side, or reading never ends
+    drop(command);
+    drop(slave);
+
+    let raw_terminal = if io::stdin().is_terminal() {
+        forward_input(&master)?
+    } else {
+        None
+    };
+
+    Ok((child, File::from(master), raw_terminal))
+}
+
+// Puts the terminal in raw mode so keys go to the command as typed
+fn forward_input(master: &OwnedFd) -> Result<Option<RawTerminal>, io::Error> {
+
+    if let Ok(mut target) = INPUT_TARGET.lock() {
+        *target = Some(File::from(master.try_clone()?));
+    }
+
+    // A single reader of stdin for every command, it stays blocked there until morning ends
+    INPUT_THREAD.call_once(|| {
+        thread::spawn(|| {
+            let mut stdin = io::stdin();
+            let mut buffer = [0; 1024];
+
+            while let Ok(count) = stdin.read(&mut buffer) {
+                if count == 0 {
+                    break;
+                }
+                if let Ok(mut target) = INPUT_TARGET.lock() {
+                    if let Some(target) = target.as_mut() {
+                        let _ = target.write_all(&buffer[..count]);
+                    }
+                }
+            }
+        });
+    });
+
+    let mut original: libc::termios = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+        return Ok(None);
+    }
+
+    let mut raw = original;
+    unsafe {
+        libc::cfmakeraw(&mut raw);
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw);
+    }
+
+    Ok(Some(RawTerminal { original: original }))
+}
+
+// Size of the terminal morning runs in
+fn terminal_size() -> libc::winsize {
+
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+
+    if result != 0 || size.ws_row == 0 || size.ws_col == 0 {
+        size.ws_row = DEFAULT_ROWS;
+        size.ws_col = DEFAULT_COLS;
+    }
+
+    return size;
+}
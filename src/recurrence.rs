@@ -0,0 +1,450 @@
+use std::fmt;
+use std::str::FromStr;
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+// Recurrence rules are a small subset of the iCalendar RRULE
+// e.g. FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=1 is the first business day of the month
+
+// Limit of days scanned when looking for the next occurrence
+const MAX_SCAN_DAYS: i64 = 3660;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recurrence {
+    frequency:    Frequency,
+    interval:     u32,
+    start:        NaiveDate,
+    until:        Option<NaiveDate>,
+    by_weekday:   Vec<Weekday>,
+    by_month_day: Vec<i32>,
+    by_set_pos:   Option<i32>,
+}
+
+#[derive(Debug)]
+pub struct RecurrenceError(String);
+
+impl fmt::Display for RecurrenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid recurrence rule : {}", self.0)
+    }
+}
+
+impl std::error::Error for RecurrenceError {}
+
+impl Recurrence {
+
+    // Parse a rule, DTSTART defaults to the given date when missing
+    pub fn parse(rule: &str, default_start: Option<NaiveDate>) -> Result<Self, RecurrenceError> {
+
+        let mut frequency:    Option<Frequency> = None;
+        let mut interval:     u32 = 1;
+        let mut start:        Option<NaiveDate> = default_start;
+        let mut until:        Option<NaiveDate> = None;
+        let mut by_weekday:   Vec<Weekday> = Vec::new();
+        let mut by_month_day: Vec<i32> = Vec::new();
+        let mut by_set_pos:   Option<i32> = None;
+
+        for part in rule.split(';').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+
+            let (key, value) = match part.find('=') {
+                Some(index) => (part[..index].trim().to_uppercase(), part[index + 1..].trim()),
+                None => return Err(RecurrenceError(format!("missing value for {}", part))),
+            };
+
+            match key.as_str() {
+                "FREQ" => {
+                    frequency = Some(parse_frequency(value)?);
+                },
+                "INTERVAL" => {
+                    interval = match value.parse::<u32>() {
+                        Ok(i) if i > 0 => i,
+                        _ => return Err(RecurrenceError(format!("bad interval {}", value))),
+                    };
+                },
+                "DTSTART" => {
+                    start = Some(parse_rule_date(value)?);
+                },
+                "UNTIL" => {
+                    until = Some(parse_rule_date(value)?);
+                },
+                "BYDAY" => {
+                    for day in value.split(',') {
+                        by_weekday.push(parse_weekday(day.trim())?);
+                    }
+                },
+                "BYMONTHDAY" => {
+                    for day in value.split(',') {
+                        match day.trim().parse::<i32>() {
+                            Ok(d) if d != 0 && d.abs() <= 31 => by_month_day.push(d),
+                            _ => return Err(RecurrenceError(format!("bad month day {}", day))),
+                        }
+                    }
+                },
+                "BYSETPOS" => {
+                    by_set_pos = match value.parse::<i32>() {
+                        Ok(p) if p != 0 => Some(p),
+                        _ => return Err(RecurrenceError(format!("bad set position {}", value))),
+                    };
+                },
+                _ => {
+                    return Err(RecurrenceError(format!("unknown part {}", key)));
+                }
+            }
+        }
+
+        let frequency = frequency.ok_or_else(|| RecurrenceError(String::from("FREQ is required")))?;
+        let start = start.ok_or_else(|| RecurrenceError(String::from("DTSTART is required")))?;
+
+        Ok(
+            Recurrence {
+                frequency:    frequency,
+                interval:     interval,
+                start:        start,
+                until:        until,
+                by_weekday:   by_weekday,
+                by_month_day: by_month_day,
+                by_set_pos:   by_set_pos,
+            }
+        )
+    }
+
+    pub fn until(&self) -> Option<NaiveDate> {
+        self.until
+    }
+
+    pub fn occurs_on(&self, date: NaiveDate) -> bool {
+
+        if date < self.start {
+            return false;
+        }
+
+        if let Some(until) = self.until {
+            if date > until {
+                return false;
+            }
+        }
+
+        if !self.in_active_period(date) {
+            return false;
+        }
+
+        return self.occurrences_in_period(date).contains(&date);
+    }
+
+    pub fn next_occurrence(&self, from: NaiveDate) -> Option<NaiveDate> {
+        (0..MAX_SCAN_DAYS).map(|i| from + Duration::days(i))
+                          .find(|d| self.occurs_on(*d))
+    }
+
+    // Open bounds are limited by the start of the rule and the scan limit
+    pub fn occurs_between(&self, from: Option<NaiveDate>, to: Option<NaiveDate>) -> bool {
+
+        let begin = match from {
+            Some(from) if from > self.start => from,
+            _ => self.start,
+        };
+
+        match self.next_occurrence(begin) {
+            Some(date) => to.is_none_or(|to| date <= to),
+            None => false,
+        }
+    }
+
+    // Only every INTERVAL period can contain occurrences
+    fn in_active_period(&self, date: NaiveDate) -> bool {
+
+        let elapsed = match self.frequency {
+            Frequency::Daily => {
+                (date - self.start).num_days()
+            },
+            Frequency::Weekly => {
+                (week_start(date) - week_start(self.start)).num_days() / 7
+            },
+            Frequency::Monthly => {
+                month_index(date) - month_index(self.start)
+            },
+            Frequency::Yearly => {
+                (date.year() - self.start.year()) as i64
+            }
+        };
+
+        return elapsed % self.interval as i64 == 0;
+    }
+
+    // All dates of the period containing the given date that match the BY parts
+    fn occurrences_in_period(&self, date: NaiveDate) -> Vec<NaiveDate> {
+
+        let period: Vec<NaiveDate> = match self.frequency {
+            Frequency::Daily => {
+                vec![date]
+            },
+            Frequency::Weekly => {
+                let monday = week_start(date);
+                (0..7).map(|i| monday + Duration::days(i)).collect()
+            },
+            Frequency::Monthly => {
+                month_days(date.year(), date.month())
+            },
+            Frequency::Yearly => {
+                month_days(date.year(), self.start.month())
+            }
+        };
+
+        let no_filter = self.by_weekday.is_empty() && self.by_month_day.is_empty();
+
+        let mut candidates: Vec<NaiveDate> = period.into_iter().filter(|d| {
+            if no_filter {
+                // Without BY parts, repeat on the same day as the start
+                return match self.frequency {
+                    Frequency::Daily => true,
+                    Frequency::Weekly => d.weekday() == self.start.weekday(),
+                    Frequency::Monthly | Frequency::Yearly => d.day() == self.start.day(),
+                };
+            }
+
+            let weekday_match = self.by_weekday.is_empty() || self.by_weekday.contains(&d.weekday());
+            let month_day_match = self.by_month_day.is_empty() || self.by_month_day.iter().any(|md| matches_month_day(*d, *md));
+
+            weekday_match && month_day_match
+        }).collect();
+
+        if let Some(position) = self.by_set_pos {
+            let len = candidates.len() as i32;
+            let index = if position > 0 { position - 1 } else { len + position };
+
+            candidates = if index >= 0 && index < len {
+                vec![candidates[index as usize]]
+            } else {
+                Vec::new()
+            };
+        }
+
+        return candidates;
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+        let frequency = match self.frequency {
+            Frequency::Daily   => "DAILY",
+            Frequency::Weekly  => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly  => "YEARLY",
+        };
+
+        write!(f, "FREQ={}", frequency)?;
+
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+
+        if !self.by_weekday.is_empty() {
+            let days: Vec<&str> = self.by_weekday.iter().map(|d| weekday_code(*d)).collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+
+        if !self.by_month_day.is_empty() {
+            let days: Vec<String> = self.by_month_day.iter().map(|d| d.to_string()).collect();
+            write!(f, ";BYMONTHDAY={}", days.join(","))?;
+        }
+
+        if let Some(position) = self.by_set_pos {
+            write!(f, ";BYSETPOS={}", position)?;
+        }
+
+        write!(f, ";DTSTART={}", self.start.format("%Y-%m-%d"))?;
+
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", until.format("%Y-%m-%d"))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = RecurrenceError;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        Recurrence::parse(rule, None)
+    }
+}
+
+fn parse_frequency(value: &str) -> Result<Frequency, RecurrenceError> {
+    match value.to_uppercase().as_str() {
+        "DAILY"   => Ok(Frequency::Daily),
+        "WEEKLY"  => Ok(Frequency::Weekly),
+        "MONTHLY" => Ok(Frequency::Monthly),
+        "YEARLY"  => Ok(Frequency::Yearly),
+        _ => Err(RecurrenceError(format!("unknown frequency {}", value))),
+    }
+}
+
+fn parse_rule_date(value: &str) -> Result<NaiveDate, RecurrenceError> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y%m%d"))
+        .map_err(|_| RecurrenceError(format!("bad date {}", value)))
+}
+
+fn parse_weekday(value: &str) -> Result<Weekday, RecurrenceError> {
+    match value.to_uppercase().as_str() {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        _ => Err(RecurrenceError(format!("unknown day {}", value))),
+    }
+}
+
+fn weekday_code(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+fn month_index(date: NaiveDate) -> i64 {
+    date.year() as i64 * 12 + date.month() as i64
+}
+
+fn month_days(year: i32, month: u32) -> Vec<NaiveDate> {
+    (1..=31).filter_map(|day| NaiveDate::from_ymd_opt(year, month, day)).collect()
+}
+
+// Negative month days count from the end of the month
+fn matches_month_day(date: NaiveDate, month_day: i32) -> bool {
+    if month_day > 0 {
+        date.day() as i32 == month_day
+    } else {
+        let last_day = month_days(date.year(), date.month()).len() as i32;
+        date.day() as i32 == last_day + month_day + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_business_day_of_the_month() {
+        let rule: Recurrence = "FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=1;DTSTART=2026-01-01".parse().unwrap();
+
+        // November 2026 starts on a Sunday
+        assert!(rule.occurs_on(parse_rule_date("2026-10-01").unwrap()));
+        assert!(!rule.occurs_on(parse_rule_date("2026-11-01").unwrap()));
+        assert!(rule.occurs_on(parse_rule_date("2026-11-02").unwrap()));
+        assert!(!rule.occurs_on(parse_rule_date("2026-11-03").unwrap()));
+        assert_eq!(rule.next_occurrence(parse_rule_date("2026-10-02").unwrap()), parse_rule_date("2026-11-02").ok());
+    }
+
+    #[test]
+    fn negative_set_position_counts_from_the_end() {
+        let rule: Recurrence = "FREQ=MONTHLY;BYDAY=FR;BYSETPOS=-1;DTSTART=2026-01-01".parse().unwrap();
+
+        assert!(rule.occurs_on(parse_rule_date("2026-10-30").unwrap()));
+        assert!(!rule.occurs_on(parse_rule_date("2026-10-23").unwrap()));
+    }
+
+    #[test]
+    fn weekly_interval_skips_weeks() {
+        let rule: Recurrence = "FREQ=WEEKLY;INTERVAL=2;DTSTART=2026-10-05".parse().unwrap();
+
+        assert!(rule.occurs_on(parse_rule_date("2026-10-05").unwrap()));
+        assert!(!rule.occurs_on(parse_rule_date("2026-10-12").unwrap()));
+        assert!(rule.occurs_on(parse_rule_date("2026-10-19").unwrap()));
+        assert!(!rule.occurs_on(parse_rule_date("2026-10-20").unwrap()));
+    }
+
+    #[test]
+    fn daily_interval_counts_from_the_start() {
+        let rule: Recurrence = "FREQ=DAILY;INTERVAL=3;DTSTART=2026-10-01".parse().unwrap();
+
+        assert!(!rule.occurs_on(parse_rule_date("2026-09-28").unwrap()));
+        assert!(!rule.occurs_on(parse_rule_date("2026-10-03").unwrap()));
+        assert!(rule.occurs_on(parse_rule_date("2026-10-04").unwrap()));
+        assert_eq!(rule.next_occurrence(parse_rule_date("2026-10-05").unwrap()), parse_rule_date("2026-10-07").ok());
+    }
+
+    #[test]
+    fn last_day_of_the_month() {
+        let rule: Recurrence = "FREQ=MONTHLY;BYMONTHDAY=-1;DTSTART=2026-01-01".parse().unwrap();
+
+        assert!(rule.occurs_on(parse_rule_date("2026-02-28").unwrap()));
+        assert!(rule.occurs_on(parse_rule_date("2028-02-29").unwrap()));
+        assert!(!rule.occurs_on(parse_rule_date("2028-02-28").unwrap()));
+    }
+
+    #[test]
+    fn yearly_repeats_on_the_start_day() {
+        let rule: Recurrence = "FREQ=YEARLY;DTSTART=2020-03-14".parse().unwrap();
+
+        assert!(rule.occurs_on(parse_rule_date("2026-03-14").unwrap()));
+        assert!(!rule.occurs_on(parse_rule_date("2026-04-14").unwrap()));
+    }
+
+    #[test]
+    fn nothing_after_until() {
+        let rule: Recurrence = "FREQ=DAILY;DTSTART=2026-10-01;UNTIL=20261010".parse().unwrap();
+
+        assert!(rule.occurs_on(parse_rule_date("2026-10-10").unwrap()));
+        assert!(!rule.occurs_on(parse_rule_date("2026-10-11").unwrap()));
+        assert_eq!(rule.next_occurrence(parse_rule_date("2026-10-11").unwrap()), None);
+    }
+
+    #[test]
+    fn start_defaults_to_the_given_date() {
+        let rule = Recurrence::parse("FREQ=WEEKLY", parse_rule_date("2026-10-18").ok()).unwrap();
+
+        assert!(rule.occurs_on(parse_rule_date("2026-10-25").unwrap()));
+        assert!(!rule.occurs_on(parse_rule_date("2026-10-11").unwrap()));
+    }
+
+    #[test]
+    fn written_form_reads_back_the_same() {
+        let original: Recurrence = "freq=monthly;interval=2;byday=mo,fr;bysetpos=-1;dtstart=2026-01-01;until=2027-01-01".parse().unwrap();
+        let written = original.to_string();
+
+        assert_eq!(written, "FREQ=MONTHLY;INTERVAL=2;BYDAY=MO,FR;BYSETPOS=-1;DTSTART=2026-01-01;UNTIL=2027-01-01");
+        assert_eq!(written.parse::<Recurrence>().unwrap(), original);
+    }
+
+    #[test]
+    fn invalid_rules_are_refused() {
+        assert!("DTSTART=2026-01-01".parse::<Recurrence>().is_err());
+        assert!("FREQ=DAILY".parse::<Recurrence>().is_err());
+        assert!("FREQ=DAILY;INTERVAL=0;DTSTART=2026-01-01".parse::<Recurrence>().is_err());
+        assert!("FREQ=DAILY;BYMONTHDAY=32;DTSTART=2026-01-01".parse::<Recurrence>().is_err());
+        assert!("FREQ=HOURLY;DTSTART=2026-01-01".parse::<Recurrence>().is_err());
+        assert!("FREQ=DAILY;BYDAY=XX;DTSTART=2026-01-01".parse::<Recurrence>().is_err());
+    }
+
+    #[test]
+    fn occurs_between_bounds() {
+        let rule: Recurrence = "FREQ=DAILY;DTSTART=2026-10-01;UNTIL=2026-10-10".parse().unwrap();
+
+        assert!(rule.occurs_between(parse_rule_date("2026-09-01").ok(), parse_rule_date("2026-10-01").ok()));
+        assert!(!rule.occurs_between(parse_rule_date("2026-09-01").ok(), parse_rule_date("2026-09-30").ok()));
+        assert!(!rule.occurs_between(parse_rule_date("2026-10-11").ok(), None));
+        assert!(rule.occurs_between(None, None));
+    }
+}
@@ -1,19 +1,307 @@
+extern crate toml;
+
+use std::collections::BTreeMap;
+use std::error::Error;
 use std::fs;
+use std::io;
 use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
 
+use super::clock::Clock;
+use super::condition::Condition;
+use super::date_format;
 use super::configuration::Configuration;
 use super::editing;
+use super::storage;
+use super::template;
+
+const REMINDER_LIST_FILE:  &str = "reminders.toml";
+const REMINDER_STATE_FILE: &str = "reminder_state.json";
+
+// Which text of the pool is shown
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Rotation {
+    // Same one for the whole day, another one the next day
+    #[default]
+    Random,
+    // One after the other, each time the reminder is shown
+    Sequential,
+}
+
+// A reminder shown only when its conditions hold
+// In reminders.toml each one is a [[reminder]] table, with a text or a pool of texts
+#[derive(Deserialize, Debug, Clone)]
+pub struct ReminderDefinition {
+    // Used to snooze it, a short hash of the text when missing
+    #[serde(default)]
+    pub id:       Option<String>,
+    #[serde(default)]
+    pub text:     Option<String>,
+    #[serde(default)]
+    pub pool:     Vec<String>,
+    #[serde(default)]
+    pub rotation: Rotation,
+    // Shown one day out of N, counting from the start date
+    #[serde(default)]
+    pub every:    Option<u32>,
+    #[serde(flatten)]
+    pub when:     Condition,
+}
+
+#[derive(Deserialize)]
+struct ReminderListFile {
+    #[serde(default)]
+    reminder: Vec<ReminderDefinition>,
+}
+
+// Snoozed and done reminders, kept in the data directory
+#[derive(Serialize, Deserialize, Default)]
+struct ReminderState {
+    // Hidden before this date
+    #[serde(default, with = "date_format::date_map")]
+    snoozed: BTreeMap<String, NaiveDate>,
+    // Day it was marked done
+    #[serde(default, with = "date_format::date_map")]
+    done:    BTreeMap<String, NaiveDate>,
+}
+
+impl ReminderDefinition {
+
+    pub fn id(&self) -> String {
+        match &self.id {
+            Some(id) => id.clone(),
+            None if self.pool.is_empty() => text_id(self.text.as_deref().unwrap_or_default()),
+            None => text_id(&self.pool.join("\n")),
+        }
+    }
+
+    // With a schedule, done only hides it until the next time it is due
+    fn is_scheduled(&self) -> bool {
+        self.every.is_some() || !self.when.weekdays.is_empty()
+    }
+
+    // The text to show on this date, None when it is not due
+    pub fn text_for(&self, today: NaiveDate) -> Option<&String> {
+
+        if self.when.unmet(today).is_some() {
+            return None;
+        }
+
+        // Without a start date, days are counted from the same fixed day for everyone
+        let days = match self.when.from {
+            Some(from) => (today - from).num_days(),
+            None => today.num_days_from_ce() as i64,
+        };
+
+        let every = self.every.unwrap_or(1).max(1) as i64;
+
+        if days.rem_euclid(every) != 0 {
+            return None;
+        }
+
+        if self.pool.is_empty() {
+            return self.text.as_ref();
+        }
+
+        let index = match self.rotation {
+            Rotation::Random => day_hash(today, &self.pool[0]) % self.pool.len() as u64,
+            Rotation::Sequential => (self.times_due_before(today, days, every) as u64) % self.pool.len() as u64,
+        };
+
+        self.pool.get(index as usize)
+    }
+
+    // How many times it was due since the day counted from, given it is due today
+    // Due days are every N days from there, a weekday filter keeps a pattern repeating every 7 of them
+    fn times_due_before(&self, today: NaiveDate, days: i64, every: i64) -> i64 {
+
+        let count = days.div_euclid(every);
+
+        if self.when.weekdays.is_empty() {
+            return count;
+        }
+
+        let first_weekday = (today.weekday().num_days_from_monday() as i64 - days).rem_euclid(7);
+        let is_due = |k: i64| {
+            let weekday = (first_weekday + k * (every % 7)).rem_euclid(7);
+            self.when.weekdays.iter().any(|w| w.num_days_from_monday() as i64 == weekday)
+        };
+
+        let due_per_week = (0..7).filter(|k| is_due(*k)).count() as i64;
+        let due_in_rest = (0..count % 7).filter(|k| is_due(*k)).count() as i64;
+
+        return (count / 7) * due_per_week + due_in_rest;
+    }
+}
+
+impl ReminderState {
+
+    fn load(conf: &Configuration) -> Result<Self, Box<dyn Error>> {
+
+        let path = reminder_state_path(conf);
+
+        if fs::metadata(&path).is_err() {
+            return Ok(ReminderState::default());
+        }
+
+        let json_string = fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&json_string)?)
+    }
+
+    // Snoozes that are over are forgotten, unless the date was given with --date
+    fn write(&mut self, conf: &Configuration, clock: &Clock) -> Result<(), Box<dyn Error>> {
+
+        if !clock.is_fixed() {
+            let today = clock.today();
+            self.snoozed.retain(|_, until| *until > today);
+        }
+
+        let json_string = serde_json::to_string(&self)?;
+        storage::write_atomic(&reminder_state_path(conf), json_string.as_bytes())?;
+
+        Ok(())
+    }
+
+    // Why the reminder is hidden today, None when it is shown
+    fn hidden(&self, id: &str, scheduled: bool, today: NaiveDate) -> Option<String> {
+
+        if let Some(until) = self.snoozed.get(id) {
+            if *until > today {
+                return Some(format!("snoozed until {}", until));
+            }
+        }
+
+        if let Some(done) = self.done.get(id) {
+            if !scheduled || *done >= today {
+                return Some(format!("done on {}", done));
+            }
+        }
+
+        None
+    }
+}
+
+// Stable from one run to the other, so the pick only changes with the day
+fn day_hash(date: NaiveDate, seed: &str) -> u64 {
+
+    let mut bytes: Vec<u8> = seed.bytes().collect();
+    bytes.extend_from_slice(&date.num_days_from_ce().to_le_bytes());
+
+    let mut hash = fnv_hash(&bytes);
+
+    // Mix the bits so following days do not give following picks
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+
+    return hash;
+}
+
+// Short ID of a reminder without one, it changes when the text changes
+fn text_id(text: &str) -> String {
+    format!("{:06x}", fnv_hash(text.trim().as_bytes()) & 0xffffff)
+}
+
+// FNV-1a
+fn fnv_hash(bytes: &[u8]) -> u64 {
+
+    let mut hash: u64 = 0xcbf29ce484222325;
+
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    return hash;
+}
+
+// Path of reminders.toml, next to the reminder file
+pub fn reminder_list_path(conf: &Configuration) -> PathBuf {
+    conf.reminder_file().with_file_name(REMINDER_LIST_FILE)
+}
+
+// Path of reminder_state.json, next to the history
+fn reminder_state_path(conf: &Configuration) -> PathBuf {
+    conf.history_path().with_file_name(REMINDER_STATE_FILE)
+}
 
-pub fn print_reminder(conf: &Configuration){
+pub fn load_reminder_list(conf: &Configuration) -> Result<Vec<ReminderDefinition>, Box<dyn Error>> {
+
+    let path = reminder_list_path(conf);
+
+    if fs::metadata(&path).is_err() {
+        return Ok(Vec::new());
+    }
+
+    let toml_string = fs::read_to_string(&path)?;
+    let list: ReminderListFile = toml::from_str(&toml_string)?;
+
+    Ok(list.reminder)
+}
+
+// Every line of the reminder file is a reminder of its own
+fn load_plain_reminders(conf: &Configuration) -> Vec<String> {
+    match fs::read_to_string(conf.reminder_file()) {
+        Ok(reminders) => reminders.lines().filter(|line| !line.trim().is_empty()).map(String::from).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+// The reminder file is always shown, the reminders of the list only when due today
+// Snoozed and done reminders are left out
+pub fn print_reminder(conf: &Configuration, today: NaiveDate){
 
     let reminder_string = fs::read_to_string(conf.reminder_file());
 
+    let mut header_printed = false;
+    let mut print_header = || {
+        if !header_printed {
+            println!("------------------ Reminders --------------------");
+            header_printed = true;
+        }
+    };
+
+    let state = match ReminderState::load(conf) {
+        Ok(state) => state,
+        Err(e) => {
+            print_header();
+            println!("Cannot read {}, every reminder is shown", reminder_state_path(conf).display());
+            println!("{}", e);
+            ReminderState::default()
+        }
+    };
+
     match reminder_string {
         Ok(reminders) => {
+            // Blank lines are kept so the file looks as written, unless what they separated is hidden
+            let mut shown: Vec<&str> = Vec::new();
+
+            for line in reminders.lines() {
+                if line.trim().is_empty() {
+                    if shown.last().is_some_and(|last| !last.trim().is_empty()) {
+                        shown.push(line);
+                    }
+                } else if state.hidden(&text_id(line), false, today).is_none() {
+                    shown.push(line);
+                }
+            }
+
+            while shown.last().is_some_and(|last| last.trim().is_empty()) {
+                shown.pop();
+            }
+
+            if reminders.ends_with('\n') {
+                shown.push("");
+            }
+
             // Do not print the section if there is nothing to write
-            if !reminders.is_empty() {
-                println!("------------------ Reminders --------------------");
-                println!("{}", reminders);
+            if shown.iter().any(|line| !line.trim().is_empty()) {
+                print_header();
+                println!("{}", template::render(&shown.join("\n"), today));
             }
         },
         Err(e) => {
@@ -22,20 +310,158 @@ pub fn print_reminder(conf: &Configuration){
                     // No file is present, so just ignore this section
                 },
                 ErrorKind::PermissionDenied => {
-                    println!("------------------ Reminders --------------------");
-                    println!("Cannot read the file {}, permission denied", 
+                    print_header();
+                    println!("Cannot read the file {}, permission denied",
                              conf.reminder_file().display());
                 }
                 _ => {
-                    println!("------------------ Reminders --------------------");
+                    print_header();
                     println!("Cannot read reminder file");
                     println!("{}", e);
                 }
             }
         }
     }
+
+    match load_reminder_list(conf) {
+        Ok(reminders) => {
+            for reminder in &reminders {
+                if state.hidden(&reminder.id(), reminder.is_scheduled(), today).is_some() {
+                    continue;
+                }
+                if let Some(text) = reminder.text_for(today) {
+                    print_header();
+                    println!("{}", template::render(text, today));
+                }
+            }
+        },
+        Err(e) => {
+            print_header();
+            println!("Cannot read {}", reminder_list_path(conf).display());
+            println!("{}", e);
+        }
+    }
+}
+
+// Every reminder with its ID and why it is hidden today
+pub fn print_reminder_list(conf: &Configuration, today: NaiveDate) -> Result<(), Box<dyn Error>> {
+
+    let state = ReminderState::load(conf)?;
+    let plain = load_plain_reminders(conf);
+    let reminders = load_reminder_list(conf)?;
+
+    if plain.is_empty() && reminders.is_empty() {
+        println!("No reminders");
+    }
+
+    for line in &plain {
+        let id = text_id(line);
+        match state.hidden(&id, false, today) {
+            Some(reason) => println!("[{}] {} ({})", id, line.trim(), reason),
+            None => println!("[{}] {}", id, line.trim()),
+        }
+    }
+
+    for reminder in &reminders {
+        let id = reminder.id();
+
+        let text = match (&reminder.text, reminder.pool.len()) {
+            (_, 0) => reminder.text.clone().unwrap_or_default(),
+            (Some(text), _) => text.clone(),
+            (None, count) => format!("Pool of {} reminders", count),
+        };
+
+        let reason = state.hidden(&id, reminder.is_scheduled(), today).or_else(|| {
+            match reminder.text_for(today) {
+                Some(_) => None,
+                None => Some(reminder.when.unmet(today).unwrap_or(String::from("not due today"))),
+            }
+        });
+
+        match reason {
+            Some(reason) => println!("[{}] {} ({})", id, text, reason),
+            None => println!("[{}] {}", id, text),
+        }
+    }
+
+    Ok(())
+}
+
+// Hidden before the date, shown again on it
+pub fn snooze_reminder(conf: &Configuration, id: &str, until: NaiveDate, clock: &Clock) -> Result<(), Box<dyn Error>> {
+
+    check_reminder_id(conf, id)?;
+
+    let mut state = ReminderState::load(conf)?;
+    state.snoozed.insert(String::from(id), until);
+    state.write(conf, clock)
+}
+
+// Hidden for good, or until it is due again when it has a schedule
+pub fn complete_reminder(conf: &Configuration, id: &str, clock: &Clock) -> Result<(), Box<dyn Error>> {
+
+    check_reminder_id(conf, id)?;
+
+    let mut state = ReminderState::load(conf)?;
+    state.snoozed.remove(id);
+    state.done.insert(String::from(id), clock.today());
+    state.write(conf, clock)
+}
+
+fn check_reminder_id(conf: &Configuration, id: &str) -> Result<(), Box<dyn Error>> {
+
+    let known = load_plain_reminders(conf).iter().any(|line| text_id(line) == id)
+                || load_reminder_list(conf)?.iter().any(|reminder| reminder.id() == id);
+
+    if !known {
+        return Err(Box::new(io::Error::new(ErrorKind::NotFound, format!("No reminder with ID {}", id))));
+    }
+
+    Ok(())
 }
 
 pub fn edit_reminder(conf: &Configuration) {
     editing::edit_file(conf.reminder_file());
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Texts shown over the first days of the reminder, with the day number since its start
+    fn shown(weekdays: &str, every: Option<u32>, days: i64) -> Vec<(i64, String)> {
+        let mut reminder: ReminderDefinition = toml::from_str(&format!(
+            "pool = [\"a\", \"b\", \"c\", \"d\", \"e\", \"f\", \"g\"]\nrotation = \"sequential\"\nweekdays = [{}]\nfrom = \"2026-10-05\"",
+            weekdays
+        )).unwrap();
+        reminder.every = every;
+
+        let start = reminder.when.from.unwrap();
+
+        (0..days).filter_map(|i| reminder.text_for(start + chrono::Duration::days(i)).map(|text| (i, text.clone())))
+                 .collect()
+    }
+
+    #[test]
+    fn sequential_moves_on_each_due_weekday() {
+        // The start is a Monday
+        let shown = shown("\"mon\"", None, 28);
+
+        assert_eq!(shown, [(0, String::from("a")), (7, String::from("b")), (14, String::from("c")), (21, String::from("d"))]);
+    }
+
+    #[test]
+    fn sequential_counts_weekdays_with_every() {
+        // Every 3 days from a Monday, only on Monday or Thursday
+        let shown = shown("\"mon\", \"thu\"", Some(3), 42);
+
+        assert_eq!(shown, [(0, String::from("a")), (3, String::from("b")), (21, String::from("c")), (24, String::from("d"))]);
+    }
+
+    #[test]
+    fn sequential_without_weekdays_follows_every() {
+        let shown = shown("", Some(2), 5);
+
+        assert_eq!(shown, [(0, String::from("a")), (2, String::from("b")), (4, String::from("c"))]);
+    }
+}
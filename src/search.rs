@@ -0,0 +1,125 @@
+use std::error::Error;
+use std::io::{self, IsTerminal};
+use chrono::NaiveDate;
+use regex::{Regex, RegexBuilder};
+
+use super::dates::{DateSpec, Direction, DateError};
+
+const HIGHLIGHT_START: &str = "\x1b[1;31m";
+const HIGHLIGHT_END:   &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchMode {
+    Plain,
+    IgnoreCase,
+    Regex,
+}
+
+#[derive(Debug)]
+enum Matcher {
+    Plain(String),
+    Regex(Regex),
+}
+
+#[derive(Debug)]
+pub struct Search {
+    matcher:  Matcher,
+    from:     Option<DateSpec>,
+    to:       Option<DateSpec>,
+    archived: bool,
+}
+
+impl Search {
+
+    pub fn new(pattern: &str, mode: SearchMode, from: Option<DateSpec>, to: Option<DateSpec>, archived: bool) -> Result<Self, Box<dyn Error>> {
+
+        let matcher = match mode {
+            SearchMode::Plain => {
+                Matcher::Plain(String::from(pattern))
+            },
+            SearchMode::IgnoreCase => {
+                Matcher::Regex(RegexBuilder::new(&regex::escape(pattern)).case_insensitive(true).build()?)
+            },
+            SearchMode::Regex => {
+                Matcher::Regex(Regex::new(pattern)?)
+            }
+        };
+
+        Ok(
+            Search {
+                matcher:  matcher,
+                from:     from,
+                to:       to,
+                archived: archived,
+            }
+        )
+    }
+
+    // Also look into the messages moved to the archive
+    pub fn include_archives(&self) -> bool {
+        self.archived
+    }
+
+    // Relative bounds without a sign count back from today
+    pub fn date_range(&self, today: NaiveDate) -> Result<(Option<NaiveDate>, Option<NaiveDate>), DateError> {
+
+        let from = match &self.from {
+            Some(from) => Some(from.resolve(today, Direction::Past)?),
+            None => None,
+        };
+
+        let to = match &self.to {
+            Some(to) => Some(to.resolve(today, Direction::Past)?),
+            None => None,
+        };
+
+        Ok((from, to))
+    }
+
+    // Byte ranges of every hit in the text
+    pub fn find(&self, text: &str) -> Vec<(usize, usize)> {
+        match &self.matcher {
+            Matcher::Plain(pattern) => {
+                if pattern.is_empty() {
+                    return Vec::new();
+                }
+                text.match_indices(pattern.as_str())
+                    .map(|(start, hit)| (start, start + hit.len()))
+                    .collect()
+            },
+            Matcher::Regex(regex) => {
+                regex.find_iter(text)
+                     .filter(|m| !m.as_str().is_empty())
+                     .map(|m| (m.start(), m.end()))
+                     .collect()
+            }
+        }
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        !self.find(text).is_empty()
+    }
+
+    // Hits are only coloured when printing to a terminal
+    pub fn highlight(&self, text: &str) -> String {
+
+        if !io::stdout().is_terminal() {
+            return String::from(text);
+        }
+
+        let mut highlighted = String::new();
+        let mut last = 0;
+
+        for (start, end) in self.find(text) {
+            highlighted.push_str(&text[last..start]);
+            highlighted.push_str(HIGHLIGHT_START);
+            highlighted.push_str(&text[start..end]);
+            highlighted.push_str(HIGHLIGHT_END);
+            last = end;
+        }
+
+        highlighted.push_str(&text[last..]);
+
+        return highlighted;
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// Set by the wrapper of --shell-init, the run writes there what the parent shell must apply
+const SHELL_FILE_VARIABLE: &str = "MORNING_SHELL_FILE";
+const SHELL_VARIABLE:      &str = "MORNING_SHELL";
+
+// Variables that belong to the shell running the command file, not to the user
+const IGNORED_VARIABLES: [&str; 9] = ["_", "PWD", "OLDPWD", "SHLVL", "SHELLOPTS", "BASHOPTS", "PS1", SHELL_FILE_VARIABLE, SHELL_VARIABLE];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(Debug)]
+pub struct UnknownShell(String);
+
+impl fmt::Display for UnknownShell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Unknown shell : {}, use bash, zsh or fish", self.0)
+    }
+}
+
+impl std::error::Error for UnknownShell {}
+
+impl FromStr for Shell {
+    type Err = UnknownShell;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(UnknownShell(String::from(name))),
+        }
+    }
+}
+
+impl Shell {
+    fn name(&self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+        }
+    }
+}
+
+// Function replacing the morning command, to use with eval in the shell startup file
+pub fn init_script(shell: Shell) -> String {
+    match shell {
+        Shell::Bash | Shell::Zsh => {
+            format!(
+"morning() {{
+    local morning_file
+    morning_file=\"$(mktemp)\" || return
+    {file}=\"$morning_file\" {shell_var}={shell} command morning \"$@\"
+    local morning_status=$?
+    if [ -s \"$morning_file\" ]; then
+        . \"$morning_file\"
+    fi
+    rm -f \"$morning_file\"
+    return $morning_status
+}}
+", file = SHELL_FILE_VARIABLE, shell_var = SHELL_VARIABLE, shell = shell.name())
+        },
+        Shell::Fish => {
+            format!(
+"function morning
+    set -l morning_file (mktemp); or return
+    env {file}=$morning_file {shell_var}=fish morning $argv
+    set -l morning_status $status
+    if test -s $morning_file
+        source $morning_file
+    end
+    rm -f $morning_file
+    return $morning_status
+end
+", file = SHELL_FILE_VARIABLE, shell_var = SHELL_VARIABLE)
+        }
+    }
+}
+
+// Runs morning --once from the prompt, checked again every hour so a long lived shell
+// still gets the routine of the next day, the date itself is left to morning
+pub fn hook_script(shell: Shell) -> &'static str {
+    match shell {
+        Shell::Bash => {
+"__morning_hook() {
+    local morning_status=$?
+    local morning_hour
+    morning_hour=$(date '+%F %H')
+    if [ \"$morning_hour\" != \"$__morning_last_hour\" ]; then
+        __morning_last_hour=\"$morning_hour\"
+        morning --once
+    fi
+    return $morning_status
+}
+case \";${PROMPT_COMMAND};\" in
+    *\";__morning_hook;\"*) ;;
+    *) PROMPT_COMMAND=\"__morning_hook${PROMPT_COMMAND:+;$PROMPT_COMMAND}\" ;;
+esac
+"
+        },
+        Shell::Zsh => {
+"__morning_hook() {
+    local morning_hour=${(%):-%D{%F %H}}
+    if [[ \"$morning_hour\" != \"$__morning_last_hour\" ]]; then
+        __morning_last_hour=\"$morning_hour\"
+        morning --once
+    fi
+}
+autoload -Uz add-zsh-hook
+add-zsh-hook precmd __morning_hook
+"
+        },
+        Shell::Fish => {
+"function __morning_hook --on-event fish_prompt
+    set -l morning_hour (date '+%F %H')
+    if test \"$morning_hour\" != \"$__morning_last_hour\"
+        set -g __morning_last_hour $morning_hour
+        morning --once
+    end
+end
+"
+        }
+    }
+}
+
+// The shell the wrapper runs in, when morning was started by it
+fn target_shell() -> Option<(Shell, PathBuf)> {
+
+    let file = env::var_os(SHELL_FILE_VARIABLE)?;
+    let shell = env::var(SHELL_VARIABLE).ok()?.parse::<Shell>().ok()?;
+
+    Some((shell, PathBuf::from(file)))
+}
+
+// Only interpreters reading sh syntax can source the command file and report their state
+// They report it in the file of the wrapper, made by mktemp so no one else can own or swap it
+pub fn capture_path(interpreter: &[String]) -> Option<PathBuf> {
+
+    let (_, shell_file) = target_shell()?;
+
+    let mut programs = interpreter.iter().map(|word| {
+        Path::new(word).file_name().map_or(String::new(), |name| name.to_string_lossy().to_string())
+    });
+
+    let mut program = programs.next().unwrap_or_default();
+
+    // #!/usr/bin/env bash
+    if program == "env" {
+        program = programs.next().unwrap_or_default();
+    }
+
+    match matches!(program.as_str(), "bash" | "sh" | "zsh" | "dash" | "ksh") {
+        true => Some(shell_file),
+        false => None,
+    }
+}
+
+// Script given to -c, with the command file as $0 and the capture file as $1
+pub fn capture_script() -> &'static str {
+    ". \"$0\"; morning_status=$?; { pwd; env -0; } > \"$1\"; exit $morning_status"
+}
+
+// Replaces the capture with what changed between morning and the end of the command file
+// for the wrapper to source
+pub fn apply_capture(capture: &Path) -> Result<(), io::Error> {
+
+    let shell = match target_shell() {
+        Some((shell, _)) => shell,
+        None => return Ok(()),
+    };
+
+    let contents = fs::read(capture)?;
+
+    // The command file was stopped before it could report anything
+    if contents.is_empty() {
+        return Ok(());
+    }
+
+    let split = contents.iter().position(|byte| *byte == b'\n').unwrap_or(contents.len());
+    let directory = String::from_utf8_lossy(&contents[..split]).to_string();
+
+    let mut variables: BTreeMap<String, String> = BTreeMap::new();
+
+    for entry in contents[(split + 1).min(contents.len())..].split(|byte| *byte == 0) {
+        let entry = String::from_utf8_lossy(entry);
+        if let Some((name, value)) = entry.split_once('=') {
+            variables.insert(String::from(name), String::from(value));
+        }
+    }
+
+    let mut lines: Vec<String> = Vec::new();
+
+    for (name, value) in &variables {
+        if IGNORED_VARIABLES.contains(&name.as_str()) || name.starts_with("BASH_") || !is_name(name) {
+            continue;
+        }
+        if env::var(name).ok().as_ref() != Some(value) {
+            lines.push(export_line(shell, name, value));
+        }
+    }
+
+    for (name, _) in env::vars() {
+        if !variables.contains_key(&name) && !IGNORED_VARIABLES.contains(&name.as_str()) && is_name(&name) {
+            lines.push(unset_line(shell, &name));
+        }
+    }
+
+    let current_dir = env::current_dir()?;
+
+    if !directory.is_empty() && Path::new(&directory) != current_dir {
+        lines.push(format!("cd {}", quote(shell, &directory)));
+    }
+
+    let mut script = lines.join("\n");
+    if !script.is_empty() {
+        script.push('\n');
+    }
+
+    fs::write(capture, script)
+}
+
+// Exported bash functions and the like cannot be set back
+fn is_name(name: &str) -> bool {
+    !name.starts_with(|c: char| c.is_ascii_digit())
+        && !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn export_line(shell: Shell, name: &str, value: &str) -> String {
+    match shell {
+        Shell::Bash | Shell::Zsh => format!("export {}={}", name, quote(shell, value)),
+        Shell::Fish => format!("set -gx {} {}", name, quote(shell, value)),
+    }
+}
+
+fn unset_line(shell: Shell, name: &str) -> String {
+    match shell {
+        Shell::Bash | Shell::Zsh => format!("unset {}", name),
+        Shell::Fish => format!("set -e {}", name),
+    }
+}
+
+// Single quotes, where only the quote itself needs care
+fn quote(shell: Shell, value: &str) -> String {
+    match shell {
+        Shell::Bash | Shell::Zsh => format!("'{}'", value.replace('\'', "'\\''")),
+        Shell::Fish => format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'")),
+    }
+}
@@ -0,0 +1,126 @@
+use std::fs;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const LOCK_FILE:    &str = "morning.lock";
+const BACKUP_COUNT: u32 = 3;
+
+// Held for the whole run so two mornings cannot edit the same files
+// The advisory lock is released when the file is closed on drop
+pub struct DataLock {
+    _file: File,
+}
+
+pub fn lock_data_dir(data_dir: &Path) -> Result<DataLock, io::Error> {
+
+    let file = open_lock_file(data_dir)?;
+
+    match file.try_lock() {
+        Ok(_) => {},
+        Err(TryLockError::WouldBlock) => {
+            println!("Waiting for another morning to finish");
+            file.lock()?;
+        },
+        Err(TryLockError::Error(e)) => {
+            return Err(e);
+        }
+    }
+
+    Ok(DataLock { _file: file })
+}
+
+// Same without waiting, None when another morning holds it
+pub fn try_lock_data_dir(data_dir: &Path) -> Result<Option<DataLock>, io::Error> {
+
+    let file = open_lock_file(data_dir)?;
+
+    match file.try_lock() {
+        Ok(_) => Ok(Some(DataLock { _file: file })),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(e)) => Err(e),
+    }
+}
+
+// Beside the configuration file, held only while it is read or written
+pub fn lock_config(config_file: &Path) -> Result<DataLock, io::Error> {
+
+    let file_name = config_file.file_name().and_then(|n| n.to_str()).unwrap_or("morning.conf");
+    let lock_path = config_file.with_file_name(format!("{}.lock", file_name));
+
+    if let Some(parent) = lock_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let file = OpenOptions::new().create(true).truncate(false).write(true).open(&lock_path)?;
+    file.lock()?;
+
+    Ok(DataLock { _file: file })
+}
+
+fn open_lock_file(data_dir: &Path) -> Result<File, io::Error> {
+
+    fs::create_dir_all(data_dir)?;
+
+    let mut lock_path = PathBuf::from(data_dir);
+    lock_path.push(LOCK_FILE);
+
+    OpenOptions::new().create(true).truncate(false).write(true).open(&lock_path)
+}
+
+// Write in a temporary file then rename it, so the file is never half written
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), io::Error> {
+
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("morning");
+    let temp_path = path.with_file_name(format!(".{}.tmp", file_name));
+
+    let mut temp_file = File::create(&temp_path)?;
+    temp_file.write_all(contents)?;
+    temp_file.sync_all()?;
+
+    fs::rename(&temp_path, path)?;
+
+    Ok(())
+}
+
+// Same as write_atomic, keeping the previous versions as file.1 (newest) to file.3
+pub fn write_with_backup(path: &Path, contents: &[u8]) -> Result<(), io::Error> {
+
+    // Nothing changed, keep the backups as they are
+    if let Ok(current) = fs::read(path) {
+        if current == contents {
+            return Ok(());
+        }
+    }
+
+    if fs::metadata(path).is_ok() {
+        for i in (1..BACKUP_COUNT).rev() {
+            let older = backup_path(path, i);
+            if fs::metadata(&older).is_ok() {
+                fs::rename(&older, backup_path(path, i + 1))?;
+            }
+        }
+
+        // Copy rather than rename so the live file is always there
+        fs::copy(path, backup_path(path, 1))?;
+    }
+
+    write_atomic(path, contents)
+}
+
+// A leading ~ is the home directory
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(mut home)) => {
+            home.push(rest);
+            home
+        },
+        _ => path.to_path_buf()
+    }
+}
+
+fn backup_path(path: &Path, index: u32) -> PathBuf {
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("morning");
+    path.with_file_name(format!("{}.{}", file_name, index))
+}
@@ -0,0 +1,110 @@
+use std::env;
+
+use chrono::{Datelike, NaiveDate};
+
+use super::condition;
+
+const OPEN:  &str = "{{";
+const CLOSE: &str = "}}";
+
+// Replaces the placeholders of a reminder or message as of this date
+// {{date}} {{weekday}} {{week_number}} {{days_until DATE}} {{days_since DATE}} {{env NAME}} {{hostname}}
+// A placeholder that cannot be filled is left as written
+pub fn render(text: &str, today: NaiveDate) -> String {
+
+    let mut rendered = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find(OPEN) {
+        let after_open = &rest[start + OPEN.len()..];
+
+        let end = match after_open.find(CLOSE) {
+            Some(end) => end,
+            None => break,
+        };
+
+        rendered.push_str(&rest[..start]);
+
+        match expand(after_open[..end].trim(), today) {
+            Some(value) => rendered.push_str(&value),
+            None => rendered.push_str(&rest[start..start + OPEN.len() + end + CLOSE.len()]),
+        }
+
+        rest = &after_open[end + CLOSE.len()..];
+    }
+
+    rendered.push_str(rest);
+
+    return rendered;
+}
+
+fn expand(placeholder: &str, today: NaiveDate) -> Option<String> {
+
+    let mut words = placeholder.split_whitespace();
+    let name = words.next()?;
+    let argument = words.next();
+
+    if words.next().is_some() {
+        return None;
+    }
+
+    match (name, argument) {
+        ("date", None) => Some(today.format("%Y-%m-%d").to_string()),
+        ("weekday", None) => Some(today.format("%A").to_string()),
+        ("week_number", None) => Some(today.iso_week().week().to_string()),
+        ("days_until", Some(date)) => Some((parse_date(date)? - today).num_days().to_string()),
+        ("days_since", Some(date)) => Some((today - parse_date(date)?).num_days().to_string()),
+        ("env", Some(variable)) => env::var(variable).ok(),
+        ("hostname", None) => condition::hostname(),
+        _ => None,
+    }
+}
+
+fn parse_date(date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A Sunday of ISO week 42
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, 18).unwrap()
+    }
+
+    #[test]
+    fn date_placeholders() {
+        assert_eq!(render("{{date}} {{ weekday }} week {{week_number}}", today()), "2026-10-18 Sunday week 42");
+    }
+
+    #[test]
+    fn days_until_and_since() {
+        assert_eq!(render("{{days_until 2026-12-25}} days left", today()), "68 days left");
+        assert_eq!(render("{{days_since 2026-10-01}}", today()), "17");
+        assert_eq!(render("{{days_until 2026-10-01}}", today()), "-17");
+    }
+
+    #[test]
+    fn environment_variables() {
+        let path = env::var("PATH").unwrap_or_default();
+
+        assert_eq!(render("{{env PATH}}", today()), path);
+        assert_eq!(render("{{env MORNING_TEMPLATE_TEST_UNSET}}", today()), "{{env MORNING_TEMPLATE_TEST_UNSET}}");
+    }
+
+    #[test]
+    fn unknown_placeholders_are_left_as_written() {
+        assert_eq!(render("a {{nope}} b", today()), "a {{nope}} b");
+        assert_eq!(render("{{days_until soon}}", today()), "{{days_until soon}}");
+        assert_eq!(render("{{date extra}}", today()), "{{date extra}}");
+        assert_eq!(render("{{days_until}}", today()), "{{days_until}}");
+        assert_eq!(render("{{}}", today()), "{{}}");
+    }
+
+    #[test]
+    fn unclosed_placeholder_is_kept() {
+        assert_eq!(render("{{date}} and {{date", today()), "2026-10-18 and {{date");
+        assert_eq!(render("no placeholder", today()), "no placeholder");
+    }
+}